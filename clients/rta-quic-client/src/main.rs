@@ -1,71 +1,154 @@
-use quinn::{ClientConfig, Endpoint};
-use std::{net::ToSocketAddrs, sync::Arc};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use quinn::{ClientConfig, Connection, Endpoint};
+use quinn::RecvStream;
+use realtime_auth_idp::infrastructure::framing::{FrameCodec, Message, MessageType};
+use realtime_auth_idp::infrastructure::quic_server::{
+    ChallengeRequest, ChallengeResponse, ErrorResponse, TokenExchangeRequest,
+    TokenExchangeResponse, ALPN_RTA, GRANT_TYPE_EXCHANGE,
+};
+use serde::de::DeserializeOwned;
+use std::{env, fs, net::SocketAddr, net::ToSocketAddrs, sync::Arc};
 use tracing::info;
-use tracing_subscriber;
-use serde::{Serialize, Deserialize};
-use bincode;
-use std::time::{SystemTime, UNIX_EPOCH};
-
-#[derive(Debug, Serialize, Deserialize)]
-struct RTAToken {
-    header: [u8; 8],
-    session_id: [u8; 16],
-    context_hash: [u8; 32],
-    timestamp: u64,
-    signature: Vec<u8>, // placeholder signature
-}
 
+/// Exchanges an OAuth token for an RTAToken on one framed stream: a challenge,
+/// then the exchange request redeeming its nonce.
+///
+/// Configured from the environment: `RTA_SERVER` (default `127.0.0.1:8082`),
+/// `RTA_SERVER_NAME` (default `localhost`), `RTA_CA_CERT` (PEM bundle trusted for
+/// the server certificate), `RTA_OAUTH_TOKEN`, `RTA_AGENT_ID` and `RTA_PROVIDER`.
+/// Setting `RTA_CLIENT_CERT` and `RTA_CLIENT_KEY` (PEM) authenticates the agent with
+/// a client certificate.
+///
+/// `RTA_CONNECTIONS` (default 1) repeats the exchange on that many connections in
+/// turn; each after the first resumes the TLS session with a ticket from an earlier
+/// one. With `RTA_EARLY_DATA=1`, a resuming client sends its requests in 0-RTT early
+/// data, which the server holds until the handshake is confirmed, since exchanges are
+/// not replay-safe.
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt().init();
 
-    let server_addr = "127.0.0.1:4433";
-    let server_name = "localhost"; // match your server's certificate CN if validating
-
-    info!("Connecting to {}", server_addr);
-
-    // Explicit QUIC client config
-    let crypto_config = rustls::ClientConfig::builder()
-        .with_root_certificates(rustls::RootCertStore::empty())
-        .with_no_client_auth();
-
+    let server_addr = env::var("RTA_SERVER").unwrap_or_else(|_| "127.0.0.1:8082".into());
+    let server_name = env::var("RTA_SERVER_NAME").unwrap_or_else(|_| "localhost".into());
+    let ca_path = env::var("RTA_CA_CERT").unwrap_or_else(|_| "certs/ca.pem".into());
+
+    // Trust the CA that issued the server certificate.
+    let mut roots = rustls::RootCertStore::empty();
+    let mut ca_reader = std::io::BufReader::new(fs::File::open(&ca_path)?);
+    for cert in rustls_pemfile::certs(&mut ca_reader) {
+        roots.add(cert?)?;
+    }
+    let builder = rustls::ClientConfig::builder().with_root_certificates(roots);
+    let client_cert = env::var("RTA_CLIENT_CERT").ok().zip(env::var("RTA_CLIENT_KEY").ok());
+    let mut crypto_config = match &client_cert {
+        Some((cert_path, key_path)) => {
+            let mut cert_reader = std::io::BufReader::new(fs::File::open(cert_path)?);
+            let chain = rustls_pemfile::certs(&mut cert_reader).collect::<Result<Vec<_>, _>>()?;
+            let mut key_reader = std::io::BufReader::new(fs::File::open(key_path)?);
+            let key = rustls_pemfile::private_key(&mut key_reader)?
+                .ok_or_else(|| anyhow!("No private key in {}", key_path))?;
+            builder.with_client_auth_cert(chain, key)?
+        }
+        None => builder.with_no_client_auth(),
+    };
+    crypto_config.alpn_protocols = vec![ALPN_RTA.to_vec()];
+    let early_data = env::var("RTA_EARLY_DATA").is_ok_and(|v| v == "1" || v == "true");
+    crypto_config.enable_early_data = early_data;
     let client_cfg = ClientConfig::new(Arc::new(
         quinn::crypto::rustls::QuicClientConfig::try_from(crypto_config)?,
     ));
 
-    let endpoint = Endpoint::client("0.0.0.0:0".parse()?)?;
+    let mut endpoint = Endpoint::client("0.0.0.0:0".parse()?)?;
     endpoint.set_default_client_config(client_cfg);
 
-    let conn = endpoint
-        .connect(server_addr.to_socket_addrs()?.next().unwrap(), server_name)?
-        .await?;
-
-    info!("Connected!");
-
-    let (mut send, mut recv) = conn.open_bi().await?;
-
-    // Construct dummy RTAToken explicitly
-    let token = RTAToken {
-        header: *b"RTA1TOKN",
-        session_id: [0u8; 16],
-        context_hash: [0u8; 32],
-        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
-        signature: vec![],
+    let addr = server_addr.to_socket_addrs()?.next()
+        .ok_or_else(|| anyhow!("{} did not resolve", server_addr))?;
+    let connections: u32 = env::var("RTA_CONNECTIONS").ok()
+        .map(|n| n.parse())
+        .transpose()?
+        .unwrap_or(1);
+    let request = TokenExchangeRequest {
+        grant_type: GRANT_TYPE_EXCHANGE.into(),
+        oauth_token: env::var("RTA_OAUTH_TOKEN").unwrap_or_default(),
+        // Without a claim, the server issues to the client certificate's agent.
+        agent_id: env::var("RTA_AGENT_ID").unwrap_or_else(|_| match client_cert {
+            Some(_) => String::new(),
+            None => "rta-quic-client".into(),
+        }),
+        provider: env::var("RTA_PROVIDER").ok(),
+        ..Default::default()
     };
 
-    let serialized = bincode::serialize(&token)?;
+    for _ in 0..connections {
+        info!("Connecting to {}", server_addr);
+        let (conn, early) = connect(&endpoint, addr, &server_name, early_data).await?;
+        let response = match (exchange(&conn, &request).await, early) {
+            (Ok(response), _) => response,
+            // Streams opened in early data the server rejected are lost; the requests
+            // are sent again now that the handshake is complete.
+            (Err(e), Some(accepted)) => {
+                if accepted.await {
+                    return Err(e);
+                }
+                info!("Server rejected early data; retrying");
+                exchange(&conn, &request).await?
+            }
+            (Err(e), None) => return Err(e),
+        };
+        info!("RTAToken: {}", response.rtatoken.unwrap_or_default());
+        conn.close(0u32.into(), b"done");
+    }
+    endpoint.wait_idle().await;
+    Ok(())
+}
 
-    send.write_all(&serialized).await?;
-    send.finish().await?;
+/// Connects to the server. With `early_data`, a client holding a ticket from an
+/// earlier connection resumes in 0-RTT and can send requests right away; the returned
+/// future then tells whether the server accepted the early data.
+async fn connect(
+    endpoint: &Endpoint,
+    addr: SocketAddr,
+    server_name: &str,
+    early_data: bool,
+) -> Result<(Connection, Option<quinn::ZeroRttAccepted>)> {
+    let connecting = endpoint.connect(addr, server_name)?;
+    if !early_data {
+        return Ok((connecting.await?, None));
+    }
+    Ok(match connecting.into_0rtt() {
+        Ok((conn, accepted)) => {
+            info!("Resuming with 0-RTT early data");
+            (conn, Some(accepted))
+        }
+        Err(connecting) => (connecting.await?, None),
+    })
+}
 
-    info!("RTAToken sent. Awaiting response...");
+/// Asks for a challenge and redeems its nonce in an exchange, on one framed stream.
+async fn exchange(conn: &Connection, request: &TokenExchangeRequest) -> Result<TokenExchangeResponse> {
+    let codec = FrameCodec::default();
+    let (mut send, mut recv) = conn.open_bi().await?;
 
-    let mut response_buf = Vec::new();
-    recv.read_to_end(usize::MAX).await.map(|data| response_buf = data)?;
+    codec.write_message(&mut send, &ChallengeRequest {}).await?;
+    let challenge: ChallengeResponse = read_response(&codec, &mut recv).await?;
 
-    let response = String::from_utf8(response_buf)?;
-    info!("Server response: {}", response);
+    let request = TokenExchangeRequest {
+        nonce: Some(challenge.nonce),
+        ..request.clone()
+    };
+    codec.write_message(&mut send, &request).await?;
+    send.finish()?;
+    info!("Exchange request sent. Awaiting response...");
+    read_response(&codec, &mut recv).await
+}
 
-    Ok(())
+/// Reads the response to the last request, turning an error response into an error.
+async fn read_response<M: Message + DeserializeOwned>(codec: &FrameCodec, recv: &mut RecvStream) -> Result<M> {
+    let frame = codec.read_frame(recv).await?
+        .ok_or_else(|| anyhow!("Stream ended before the response"))?;
+    if frame.kind == MessageType::Error {
+        let err: ErrorResponse = frame.decode()?;
+        return Err(anyhow!("{}: {}", err.error, err.error_description.unwrap_or_default()));
+    }
+    Ok(frame.decode()?)
 }
@@ -0,0 +1,41 @@
+// fuzz/fuzz_targets/exchange_request.rs
+//! Feeds arbitrary bytes to the frame codec and the stream request and one-way message
+//! parsers, then decodes any tokens the parsed requests carry.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use realtime_auth_idp::infrastructure::framing::FrameCodec;
+use realtime_auth_idp::infrastructure::quic_server::{decode_token, OneWayMessage, StreamRequest};
+
+fuzz_target!(|data: &[u8]| {
+    let codec = FrameCodec::default();
+    let mut input = data;
+    futures::executor::block_on(async {
+        while let Ok(Some(frame)) = codec.read_frame(&mut input).await {
+            let Ok(request) = StreamRequest::from_frame(&frame) else {
+                let _ = OneWayMessage::from_frame(&frame);
+                continue;
+            };
+            match request {
+                StreamRequest::Exchange(req) => {
+                    for token in [req.parent_token, req.rtatoken].into_iter().flatten() {
+                        let _ = decode_token(&token);
+                    }
+                }
+                StreamRequest::Introspection(req) => {
+                    let _ = decode_token(req.token.trim());
+                }
+                StreamRequest::Authorize(req) => {
+                    let _ = decode_token(&req.rtatoken);
+                }
+                StreamRequest::Refresh(req) => {
+                    let _ = decode_token(&req.rtatoken);
+                }
+                StreamRequest::Revocation(req) => {
+                    let _ = decode_token(&req.token);
+                }
+                StreamRequest::Challenge(_) => {}
+            }
+        }
+    });
+});
@@ -0,0 +1,36 @@
+// fuzz/fuzz_targets/token_decode.rs
+//! Feeds arbitrary bytes to the token decoder. Anything it accepts must be the
+//! canonical encoding of the token it decodes to, and its caveats must decode or be
+//! rejected without panicking.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rta_verify::caveat::Caveat;
+use rta_verify::codec::{self, Extensions, RTATokenRef, SignedFields};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(token) = RTATokenRef::parse(data) else {
+        return;
+    };
+    // Rebuilt from the decoded fields rather than the signed bytes, so that a field the
+    // decoder skipped or misread shows up as a difference.
+    let signed = codec::encode_signed(&SignedFields {
+        header: token.header(),
+        session_id: token.session_id(),
+        context_hash: token.context_hash(),
+        timestamp: token.timestamp(),
+        extensions: &Extensions::from(token.extensions()),
+    })
+    .expect("parsed extensions fit the section");
+    let attenuation = token.attenuation();
+    let reencoded = codec::encode(&signed, token.signature(), attenuation.as_ref());
+    assert_eq!(reencoded, data, "decoder accepted a non-canonical encoding");
+
+    if let Some(attenuation) = attenuation {
+        for (kind, value) in attenuation.caveats() {
+            if let Some(caveat) = Caveat::decode(kind, value) {
+                assert_eq!(caveat.encode(), (kind, value.to_vec()));
+            }
+        }
+    }
+});
@@ -0,0 +1,182 @@
+// src/caveat.rs
+//! Macaroon-style caveats on attenuable RTATokens.
+//!
+//! The server issues an attenuable token with the tag `HMAC(root, signature)`, where
+//! `root` is the caveat root key of the signing key. A holder narrows the token by
+//! appending a caveat and replacing the tag with `HMAC(tag, caveat)`; since HMAC
+//! cannot be inverted, whoever receives the result can add further caveats but not
+//! remove any. The server recomputes the chain from the root and evaluates every
+//! caveat against the request being authorized. The root never leaves the server, so
+//! offline verifiers cannot check caveats.
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::net::IpAddr;
+use ring::hmac;
+use crate::codec::{self, CAVEAT_TAG_LEN};
+use crate::verify::VerifyError;
+
+/// Caveat TLV types.
+pub mod kind {
+    /// Requested resource must start with this prefix (UTF-8).
+    pub const RESOURCE_PREFIX: u8 = 0x01;
+    /// Requested action must be one of these (UTF-8, space-separated).
+    pub const ACTIONS: u8 = 0x02;
+    /// Token is invalid from this time on (unix seconds, `u64`).
+    pub const EXPIRES_AT: u8 = 0x03;
+    /// Client address must lie in this network: family (`4` or `6`), address, prefix length.
+    pub const IP_RANGE: u8 = 0x04;
+}
+
+/// A restriction appended to an attenuable token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Caveat {
+    ResourcePrefix(String),
+    Actions(Vec<String>),
+    ExpiresAt(u64),
+    IpRange { network: IpAddr, prefix_len: u8 },
+}
+
+/// The request a token is presented for, as far as caveats can restrict it.
+///
+/// Caveats fail closed: a caveat on a property the request leaves unset is not
+/// satisfied.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AccessRequest<'a> {
+    pub resource: Option<&'a str>,
+    pub action: Option<&'a str>,
+    pub client_ip: Option<IpAddr>,
+}
+
+impl Caveat {
+    /// TLV type and value of the caveat.
+    pub fn encode(&self) -> (u8, Vec<u8>) {
+        match self {
+            Caveat::ResourcePrefix(prefix) => (kind::RESOURCE_PREFIX, prefix.as_bytes().to_vec()),
+            Caveat::Actions(actions) => (kind::ACTIONS, actions.join(" ").into_bytes()),
+            Caveat::ExpiresAt(at) => (kind::EXPIRES_AT, at.to_be_bytes().to_vec()),
+            Caveat::IpRange { network, prefix_len } => {
+                let mut value = Vec::with_capacity(18);
+                match network {
+                    IpAddr::V4(addr) => {
+                        value.push(4);
+                        value.extend_from_slice(&addr.octets());
+                    }
+                    IpAddr::V6(addr) => {
+                        value.push(6);
+                        value.extend_from_slice(&addr.octets());
+                    }
+                }
+                value.push(*prefix_len);
+                (kind::IP_RANGE, value)
+            }
+        }
+    }
+
+    /// Parses a caveat; `None` for unknown types and malformed values.
+    pub fn decode(caveat_kind: u8, value: &[u8]) -> Option<Self> {
+        match caveat_kind {
+            kind::RESOURCE_PREFIX => Some(Caveat::ResourcePrefix(core::str::from_utf8(value).ok()?.to_owned())),
+            kind::ACTIONS => {
+                let actions = core::str::from_utf8(value).ok()?;
+                Some(Caveat::Actions(actions.split_whitespace().map(str::to_owned).collect()))
+            }
+            kind::EXPIRES_AT => Some(Caveat::ExpiresAt(u64::from_be_bytes(value.try_into().ok()?))),
+            kind::IP_RANGE => {
+                let (&family, rest) = value.split_first()?;
+                let (&prefix_len, addr) = rest.split_last()?;
+                let network = match family {
+                    4 => IpAddr::from(<[u8; 4]>::try_from(addr).ok()?),
+                    6 => IpAddr::from(<[u8; 16]>::try_from(addr).ok()?),
+                    _ => return None,
+                };
+                let max_len = if network.is_ipv4() { 32 } else { 128 };
+                (prefix_len <= max_len).then_some(Caveat::IpRange { network, prefix_len })
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `request`, made at unix time `now`, satisfies the caveat.
+    pub fn is_satisfied(&self, request: &AccessRequest<'_>, now: u64) -> bool {
+        match self {
+            Caveat::ResourcePrefix(prefix) => request.resource.is_some_and(|r| r.starts_with(prefix.as_str())),
+            Caveat::Actions(actions) => request.action.is_some_and(|a| actions.iter().any(|x| x == a)),
+            Caveat::ExpiresAt(at) => now < *at,
+            // IPv4 clients may be reported as IPv4-mapped IPv6 addresses by dual-stack sockets.
+            Caveat::IpRange { network, prefix_len } => {
+                request.client_ip.is_some_and(|ip| in_network(ip.to_canonical(), *network, *prefix_len))
+            }
+        }
+    }
+}
+
+/// The tag of an attenuable token before any caveat is added.
+pub fn initial_tag(root: &hmac::Key, signature: &[u8]) -> [u8; CAVEAT_TAG_LEN] {
+    to_tag(hmac::sign(root, signature))
+}
+
+/// The tag after appending the caveat `(caveat_kind, value)` to a chain ending in `tag`.
+pub fn chain_tag(tag: &[u8; CAVEAT_TAG_LEN], caveat_kind: u8, value: &[u8]) -> [u8; CAVEAT_TAG_LEN] {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, tag);
+    to_tag(hmac::sign(&key, &codec::encode_entry(caveat_kind, value)))
+}
+
+/// Recomputes the chain over `caveats` from `root` and checks it ends in `tag`.
+pub fn verify_chain<'a>(
+    root: &hmac::Key,
+    signature: &[u8],
+    caveats: impl Iterator<Item = (u8, &'a [u8])>,
+    tag: &[u8; CAVEAT_TAG_LEN],
+) -> bool {
+    // Only the last link is compared, in constant time, against the presented tag.
+    let mut key = root.clone();
+    let mut message = signature.to_vec();
+    for (caveat_kind, value) in caveats {
+        let link = hmac::sign(&key, &message);
+        key = hmac::Key::new(hmac::HMAC_SHA256, link.as_ref());
+        message = codec::encode_entry(caveat_kind, value);
+    }
+    hmac::verify(&key, &message, tag).is_ok()
+}
+
+/// Evaluates every caveat against `request`; unknown or malformed caveats fail.
+pub fn evaluate<'a>(
+    caveats: impl Iterator<Item = (u8, &'a [u8])>,
+    request: &AccessRequest<'_>,
+    now: u64,
+) -> Result<(), VerifyError> {
+    for (caveat_kind, value) in caveats {
+        let satisfied = Caveat::decode(caveat_kind, value)
+            .is_some_and(|caveat| caveat.is_satisfied(request, now));
+        if !satisfied {
+            return Err(VerifyError::CaveatNotSatisfied(caveat_kind));
+        }
+    }
+    Ok(())
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix_len: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => prefix_eq(&ip.octets(), &net.octets(), prefix_len),
+        (IpAddr::V6(ip), IpAddr::V6(net)) => prefix_eq(&ip.octets(), &net.octets(), prefix_len),
+        _ => false,
+    }
+}
+
+fn prefix_eq(a: &[u8], b: &[u8], prefix_len: u8) -> bool {
+    let full = usize::from(prefix_len / 8);
+    let rest = prefix_len % 8;
+    if a[..full] != b[..full] {
+        return false;
+    }
+    if rest == 0 {
+        return true;
+    }
+    let mask = 0xffu8 << (8 - rest);
+    a[full] & mask == b[full] & mask
+}
+
+fn to_tag(tag: hmac::Tag) -> [u8; CAVEAT_TAG_LEN] {
+    tag.as_ref().try_into().expect("HMAC-SHA256 tags are 32 bytes")
+}
@@ -0,0 +1,641 @@
+// src/codec.rs
+//! Canonical binary layout of an RTAToken.
+//!
+//! All integers are big-endian. Every token starts with an 8-byte header whose fourth
+//! byte selects the format version; the decoder dispatches on it:
+//!
+//! | Offset | Size | Field                                               |
+//! |--------|------|-----------------------------------------------------|
+//! | 0      | 3    | Magic `"RTA"`                                       |
+//! | 3      | 1    | Format version, ASCII (`'1'` or `'2'`)              |
+//! | 4      | 2    | Signing key ID (`u16`)                              |
+//! | 6      | 2    | v1: reserved, must be zero. v2: [`TokenFlags`]      |
+//! | 8      | 16   | Session ID                                          |
+//! | 24     | 32   | Context hash (SHA-256)                              |
+//! | 56     | 8    | Issued-at timestamp, unix seconds (`u64`)           |
+//!
+//! If [`TokenFlags::HAS_EXTENSIONS`] is set (v2 only), an extension section follows:
+//!
+//! | Offset | Size | Field                                               |
+//! |--------|------|-----------------------------------------------------|
+//! | 64     | 2    | Extension section length `N` (`u16`)                |
+//! | 66     | N    | TLV entries: type (`u8`), length (`u16`), value     |
+//!
+//! The section holds at least one entry; tokens without extensions clear the flag.
+//!
+//! Then comes a 64-byte Ed25519 signature over all preceding bytes. Without extensions
+//! a token is exactly [`TOKEN_LEN`] bytes.
+//!
+//! If [`TokenFlags::ATTENUABLE`] is set, an unsigned attenuation section follows the
+//! signature. Holders append caveats to it without contacting the server; the tag
+//! chains an HMAC over every caveat (see [`crate::caveat`]):
+//!
+//! | Size | Field                                                         |
+//! |------|---------------------------------------------------------------|
+//! | 2    | Caveat section length `M` (`u16`)                             |
+//! | M    | Caveat TLVs in the order they were added, same entry layout   |
+//! | 32   | Caveat chain tag (HMAC-SHA256)                                |
+use alloc::vec::Vec;
+use core::fmt;
+use crate::KeyId;
+
+pub const MAGIC: &[u8; 3] = b"RTA";
+pub const HEADER_LEN: usize = 8;
+pub const SESSION_ID_LEN: usize = 16;
+pub const CONTEXT_HASH_LEN: usize = 32;
+pub const TIMESTAMP_LEN: usize = 8;
+pub const SIGNATURE_LEN: usize = 64;
+
+const SESSION_ID_OFFSET: usize = HEADER_LEN;
+const CONTEXT_HASH_OFFSET: usize = SESSION_ID_OFFSET + SESSION_ID_LEN;
+const TIMESTAMP_OFFSET: usize = CONTEXT_HASH_OFFSET + CONTEXT_HASH_LEN;
+
+/// Length of the fixed fields preceding the optional extension section.
+pub const FIXED_LEN: usize = TIMESTAMP_OFFSET + TIMESTAMP_LEN;
+/// Total encoded length of a token without extensions.
+pub const TOKEN_LEN: usize = FIXED_LEN + SIGNATURE_LEN;
+/// Upper bound on the extension section, keeping tokens small enough for a single datagram.
+pub const MAX_EXTENSIONS_LEN: usize = 1024;
+/// Upper bound on the caveat section of an attenuable token.
+pub const MAX_CAVEATS_LEN: usize = 1024;
+pub const CAVEAT_TAG_LEN: usize = 32;
+/// Upper bound on a complete encoded token. Longer inputs are rejected before any
+/// field is read.
+pub const MAX_TOKEN_LEN: usize = TOKEN_LEN + 2 + MAX_EXTENSIONS_LEN + 2 + MAX_CAVEATS_LEN + CAVEAT_TAG_LEN;
+/// Upper bound on the padded Base64 encoding of a token, for rejecting oversized
+/// input before decoding it.
+pub const MAX_BASE64_TOKEN_LEN: usize = MAX_TOKEN_LEN.div_ceil(3) * 4;
+
+/// Extension TLV types.
+///
+/// Extensions carry data only. Anything a verifier must understand to validate a
+/// token safely is signalled by a header flag, so entries of unknown types can be
+/// preserved and ignored.
+pub mod ext {
+    /// Agent the token was issued to (UTF-8).
+    pub const AGENT_ID: u8 = 0x01;
+    /// Subject the agent acts for (UTF-8).
+    pub const SUBJECT: u8 = 0x02;
+    /// IdP that authenticated the subject (UTF-8).
+    pub const PROVIDER: u8 = 0x03;
+    /// Intended audience (UTF-8).
+    pub const AUDIENCE: u8 = 0x04;
+    /// One granted scope (UTF-8); may repeat.
+    pub const SCOPE: u8 = 0x05;
+    /// Re-issuance generation within the session (`u32`).
+    pub const GENERATION: u8 = 0x06;
+    /// SHA-256 of the keying material exported from the QUIC connection the token is
+    /// bound to (32 bytes). Present exactly when [`super::TokenFlags::BOUND`] is set.
+    pub const CONNECTION_BINDING: u8 = 0x07;
+    /// Session ID of the token this one was delegated from (16 bytes). Present
+    /// exactly when [`super::TokenFlags::DELEGATED`] is set.
+    pub const PARENT_SESSION: u8 = 0x08;
+    /// Position in the delegation chain; 1 for a child of an exchanged token (`u8`).
+    /// Present exactly when [`super::TokenFlags::DELEGATED`] is set.
+    pub const DELEGATION_DEPTH: u8 = 0x09;
+
+    /// Types whose value must be valid UTF-8.
+    pub(super) fn is_text(kind: u8) -> bool {
+        matches!(kind, AGENT_ID | SUBJECT | PROVIDER | AUDIENCE | SCOPE)
+    }
+
+    /// Types that may appear more than once.
+    pub(super) fn is_repeatable(kind: u8) -> bool {
+        kind == SCOPE
+    }
+
+    /// Required value length of fixed-size types.
+    pub(super) fn fixed_len(kind: u8) -> Option<usize> {
+        match kind {
+            GENERATION => Some(4),
+            CONNECTION_BINDING => Some(32),
+            PARENT_SESSION => Some(16),
+            DELEGATION_DEPTH => Some(1),
+            _ => None,
+        }
+    }
+}
+
+/// Reasons an encoded token cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenFormatError {
+    InvalidLength { expected: usize, actual: usize },
+    TooLong { max: usize, actual: usize },
+    BadMagic,
+    UnsupportedVersion(u8),
+    ReservedBitsSet,
+    UnknownFlags(u16),
+    UnsupportedFlags(u16),
+    MalformedExtensions(&'static str),
+    MalformedCaveats(&'static str),
+}
+
+impl fmt::Display for TokenFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenFormatError::InvalidLength { expected, actual } => {
+                write!(f, "invalid token length: expected {expected} bytes, got {actual}")
+            }
+            TokenFormatError::TooLong { max, actual } => {
+                write!(f, "token too long: at most {max} bytes, got {actual}")
+            }
+            TokenFormatError::BadMagic => f.write_str("invalid token magic"),
+            TokenFormatError::UnsupportedVersion(v) => write!(f, "unsupported token version {v:#04x}"),
+            TokenFormatError::ReservedBitsSet => f.write_str("reserved v1 header bytes must be zero"),
+            TokenFormatError::UnknownFlags(bits) => write!(f, "unknown token flags {bits:#06x}"),
+            TokenFormatError::UnsupportedFlags(bits) => write!(f, "token flags {bits:#06x} are not supported"),
+            TokenFormatError::MalformedExtensions(why) => write!(f, "malformed extension section: {why}"),
+            TokenFormatError::MalformedCaveats(why) => write!(f, "malformed caveat section: {why}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TokenFormatError {}
+
+/// Token format version, carried as an ASCII digit in header byte 3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TokenVersion {
+    /// Original layout; header bytes 6..8 are reserved.
+    V1,
+    /// Adds the flags field in header bytes 6..8.
+    V2,
+}
+
+impl TokenVersion {
+    /// The version current issuers produce.
+    pub const CURRENT: TokenVersion = TokenVersion::V2;
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            TokenVersion::V1 => b'1',
+            TokenVersion::V2 => b'2',
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Result<Self, TokenFormatError> {
+        match byte {
+            b'1' => Ok(TokenVersion::V1),
+            b'2' => Ok(TokenVersion::V2),
+            other => Err(TokenFormatError::UnsupportedVersion(other)),
+        }
+    }
+}
+
+/// Header flags bitfield (v2 and later).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TokenFlags(u16);
+
+impl TokenFlags {
+    /// Token was issued on behalf of another token holder.
+    pub const DELEGATED: TokenFlags = TokenFlags(0x0001);
+    /// Token payload is encrypted.
+    pub const ENCRYPTED: TokenFlags = TokenFlags(0x0002);
+    /// An extension section follows the fixed fields.
+    pub const HAS_EXTENSIONS: TokenFlags = TokenFlags(0x0004);
+    /// Token is bound to a QUIC connection and only valid with proof of that binding.
+    pub const BOUND: TokenFlags = TokenFlags(0x0008);
+    /// A caveat section follows the signature; the token is only valid with a correct
+    /// caveat chain tag.
+    pub const ATTENUABLE: TokenFlags = TokenFlags(0x0010);
+
+    const KNOWN: u16 = 0x001f;
+    /// Flags this codec can decode.
+    const SUPPORTED: u16 =
+        Self::DELEGATED.0 | Self::HAS_EXTENSIONS.0 | Self::BOUND.0 | Self::ATTENUABLE.0;
+
+    pub const fn empty() -> Self {
+        TokenFlags(0)
+    }
+
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// Parses a flags field, rejecting undefined bits and flags this codec cannot handle.
+    pub fn from_bits(bits: u16) -> Result<Self, TokenFormatError> {
+        if bits & !Self::KNOWN != 0 {
+            return Err(TokenFormatError::UnknownFlags(bits & !Self::KNOWN));
+        }
+        if bits & !Self::SUPPORTED != 0 {
+            return Err(TokenFormatError::UnsupportedFlags(bits & !Self::SUPPORTED));
+        }
+        Ok(TokenFlags(bits))
+    }
+
+    pub const fn contains(self, other: TokenFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: TokenFlags) -> Self {
+        TokenFlags(self.0 | other.0)
+    }
+}
+
+/// Decoded 8-byte token header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenHeader {
+    pub version: TokenVersion,
+    pub key_id: KeyId,
+    /// Always empty for v1 tokens.
+    pub flags: TokenFlags,
+}
+
+impl TokenHeader {
+    pub fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut out = [0u8; HEADER_LEN];
+        out[0..3].copy_from_slice(MAGIC);
+        out[3] = self.version.to_byte();
+        out[4..6].copy_from_slice(&self.key_id.to_be_bytes());
+        // v1 has no flags field; its reserved bytes stay zero.
+        if self.version >= TokenVersion::V2 {
+            out[6..8].copy_from_slice(&self.flags.bits().to_be_bytes());
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8; HEADER_LEN]) -> Result<Self, TokenFormatError> {
+        if &bytes[0..3] != MAGIC {
+            return Err(TokenFormatError::BadMagic);
+        }
+        let version = TokenVersion::from_byte(bytes[3])?;
+        let key_id = u16::from_be_bytes([bytes[4], bytes[5]]);
+        let trailer = u16::from_be_bytes([bytes[6], bytes[7]]);
+        let flags = match version {
+            TokenVersion::V1 if trailer != 0 => return Err(TokenFormatError::ReservedBitsSet),
+            TokenVersion::V1 => TokenFlags::empty(),
+            TokenVersion::V2 => TokenFlags::from_bits(trailer)?,
+        };
+        Ok(Self { version, key_id, flags })
+    }
+}
+
+/// Ordered list of extension entries.
+///
+/// Entries are kept in wire order so that decoding and re-encoding a token
+/// reproduces its signed bytes exactly; the decoder only accepts entries sorted by
+/// type, which is also the order [`Extensions::push`] maintains.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Extensions {
+    entries: Vec<(u8, Vec<u8>)>,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an entry, keeping entries sorted by type. Repeated types keep insertion order.
+    pub fn push(&mut self, kind: u8, value: impl Into<Vec<u8>>) -> &mut Self {
+        let at = self.entries.partition_point(|(k, _)| *k <= kind);
+        self.entries.insert(at, (kind, value.into()));
+        self
+    }
+
+    /// Adds a text entry unless `value` is `None`.
+    pub fn push_opt(&mut self, kind: u8, value: Option<&str>) -> &mut Self {
+        if let Some(value) = value {
+            self.push(kind, value.as_bytes());
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Value of the first entry of `kind`.
+    pub fn get(&self, kind: u8) -> Option<&[u8]> {
+        self.get_all(kind).next()
+    }
+
+    /// Values of all entries of `kind`, in order.
+    pub fn get_all(&self, kind: u8) -> impl Iterator<Item = &[u8]> {
+        self.entries.iter()
+            .filter(move |(k, _)| *k == kind)
+            .map(|(_, v)| v.as_slice())
+    }
+
+    /// Value of the first entry of `kind` as a big-endian `u32`.
+    pub fn get_u32(&self, kind: u8) -> Option<u32> {
+        self.get(kind)
+            .and_then(|v| v.try_into().ok())
+            .map(u32::from_be_bytes)
+    }
+
+    /// Text value of the first entry of `kind`. Known text types are checked for
+    /// UTF-8 when decoded, so this only returns `None` if the entry is absent.
+    pub fn get_str(&self, kind: u8) -> Option<&str> {
+        self.get(kind).and_then(|v| core::str::from_utf8(v).ok())
+    }
+
+    /// Encoded size of the entries, excluding the section length prefix.
+    fn encoded_len(&self) -> usize {
+        self.entries.iter().map(|(_, v)| 3 + v.len()).sum()
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) -> Result<(), TokenFormatError> {
+        let len = self.encoded_len();
+        if len > MAX_EXTENSIONS_LEN {
+            return Err(TokenFormatError::MalformedExtensions("extension section too large"));
+        }
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+        for (kind, value) in &self.entries {
+            out.push(*kind);
+            out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            out.extend_from_slice(value);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> From<ExtensionsRef<'a>> for Extensions {
+    fn from(exts: ExtensionsRef<'a>) -> Self {
+        Self { entries: exts.iter().map(|(k, v)| (k, v.to_vec())).collect() }
+    }
+}
+
+/// Borrowed, already-validated extension entries of a parsed token.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExtensionsRef<'a> {
+    entries: &'a [u8],
+}
+
+impl<'a> ExtensionsRef<'a> {
+    /// Validates the TLV entries in `entries` (the section without its length prefix).
+    fn parse(entries: &'a [u8]) -> Result<Self, TokenFormatError> {
+        let mut previous: Option<u8> = None;
+        let mut rest = entries;
+        while !rest.is_empty() {
+            let (kind, value, tail) = split_entry(rest)
+                .ok_or(TokenFormatError::MalformedExtensions("truncated entry"))?;
+            match previous {
+                Some(p) if p > kind => {
+                    return Err(TokenFormatError::MalformedExtensions("entries not sorted by type"));
+                }
+                Some(p) if p == kind && !ext::is_repeatable(kind) => {
+                    return Err(TokenFormatError::MalformedExtensions("duplicate entry"));
+                }
+                _ => {}
+            }
+            if ext::is_text(kind) && core::str::from_utf8(value).is_err() {
+                return Err(TokenFormatError::MalformedExtensions("text entry is not UTF-8"));
+            }
+            if ext::fixed_len(kind).is_some_and(|len| len != value.len()) {
+                return Err(TokenFormatError::MalformedExtensions("fixed-size entry has wrong length"));
+            }
+            previous = Some(kind);
+            rest = tail;
+        }
+        Ok(Self { entries })
+    }
+
+    /// Iterates over `(type, value)` pairs in wire order.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &'a [u8])> {
+        let mut rest = self.entries;
+        core::iter::from_fn(move || {
+            let (kind, value, tail) = split_entry(rest)?;
+            rest = tail;
+            Some((kind, value))
+        })
+    }
+}
+
+/// Caveats appended to an attenuable token and the tag chained over them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attenuation {
+    caveats: Vec<(u8, Vec<u8>)>,
+    tag: [u8; CAVEAT_TAG_LEN],
+}
+
+impl Attenuation {
+    /// An attenuation section without caveats, as issued by the server.
+    pub fn new(tag: [u8; CAVEAT_TAG_LEN]) -> Self {
+        Self { caveats: Vec::new(), tag }
+    }
+
+    /// Appends a caveat and replaces the tag with the one chained over it.
+    pub fn push(&mut self, kind: u8, value: Vec<u8>, tag: [u8; CAVEAT_TAG_LEN]) -> Result<(), TokenFormatError> {
+        let len: usize = self.caveats.iter().map(|(_, v)| 3 + v.len()).sum::<usize>() + 3 + value.len();
+        if len > MAX_CAVEATS_LEN {
+            return Err(TokenFormatError::MalformedCaveats("caveat section too large"));
+        }
+        self.caveats.push((kind, value));
+        self.tag = tag;
+        Ok(())
+    }
+
+    /// `(type, value)` pairs in the order the caveats were added.
+    pub fn caveats(&self) -> impl Iterator<Item = (u8, &[u8])> {
+        self.caveats.iter().map(|(k, v)| (*k, v.as_slice()))
+    }
+
+    pub fn tag(&self) -> &[u8; CAVEAT_TAG_LEN] {
+        &self.tag
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        let start = out.len();
+        out.extend_from_slice(&[0, 0]);
+        for (kind, value) in &self.caveats {
+            out.extend_from_slice(&encode_entry(*kind, value));
+        }
+        // `push` and the decoder both keep the section within MAX_CAVEATS_LEN.
+        let len = (out.len() - start - 2) as u16;
+        out[start..start + 2].copy_from_slice(&len.to_be_bytes());
+        out.extend_from_slice(&self.tag);
+    }
+}
+
+/// Encodes a single TLV entry.
+pub fn encode_entry(kind: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(3 + value.len());
+    out.push(kind);
+    out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    out.extend_from_slice(value);
+    out
+}
+
+/// Splits one TLV entry off the front of `bytes`.
+fn split_entry(bytes: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&kind, rest) = bytes.split_first()?;
+    let len = u16::from_be_bytes(rest.get(..2)?.try_into().ok()?) as usize;
+    let value = rest.get(2..2 + len)?;
+    Some((kind, value, &rest[2 + len..]))
+}
+
+/// Fields covered by the token signature, in wire order.
+pub struct SignedFields<'a> {
+    pub header: TokenHeader,
+    pub session_id: &'a [u8; SESSION_ID_LEN],
+    pub context_hash: &'a [u8; CONTEXT_HASH_LEN],
+    pub timestamp: u64,
+    /// Encoded only if the header carries [`TokenFlags::HAS_EXTENSIONS`].
+    pub extensions: &'a Extensions,
+}
+
+/// Encodes the signed portion of a token.
+pub fn encode_signed(fields: &SignedFields<'_>) -> Result<Vec<u8>, TokenFormatError> {
+    let mut out = Vec::with_capacity(TOKEN_LEN);
+    out.extend_from_slice(&fields.header.encode());
+    out.extend_from_slice(fields.session_id);
+    out.extend_from_slice(fields.context_hash);
+    out.extend_from_slice(&fields.timestamp.to_be_bytes());
+    if fields.header.flags.contains(TokenFlags::HAS_EXTENSIONS) {
+        fields.extensions.encode_into(&mut out)?;
+    }
+    Ok(out)
+}
+
+/// Encodes a complete token from its signed portion, signature and, for attenuable
+/// tokens, the attenuation section.
+pub fn encode(signed: &[u8], signature: &[u8; SIGNATURE_LEN], attenuation: Option<&Attenuation>) -> Vec<u8> {
+    let mut out = Vec::with_capacity(signed.len() + SIGNATURE_LEN);
+    out.extend_from_slice(signed);
+    out.extend_from_slice(signature);
+    if let Some(attenuation) = attenuation {
+        attenuation.encode_into(&mut out);
+    }
+    out
+}
+
+/// Borrowed view over an encoded token. Parsing validates the length, header and
+/// extension section once; field accessors then read in place without copying.
+#[derive(Debug, Clone, Copy)]
+pub struct RTATokenRef<'a> {
+    header: TokenHeader,
+    bytes: &'a [u8],
+    extensions: ExtensionsRef<'a>,
+    /// Length of the signed portion; the signature follows it.
+    signed_len: usize,
+    /// Caveat TLVs, validated for structure only.
+    caveats: &'a [u8],
+}
+
+impl<'a> RTATokenRef<'a> {
+    /// Parses `bytes`, which must be exactly one encoded token of a supported version.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, TokenFormatError> {
+        if bytes.len() > MAX_TOKEN_LEN {
+            return Err(TokenFormatError::TooLong { max: MAX_TOKEN_LEN, actual: bytes.len() });
+        }
+        let invalid_length = |expected| TokenFormatError::InvalidLength { expected, actual: bytes.len() };
+        let header_bytes: &[u8; HEADER_LEN] = bytes
+            .get(..HEADER_LEN)
+            .and_then(|h| h.try_into().ok())
+            .ok_or(invalid_length(TOKEN_LEN))?;
+        let header = TokenHeader::decode(header_bytes)?;
+
+        // Only v2 tokens can carry extensions; v1 headers always decode with empty flags.
+        let mut extensions = ExtensionsRef::default();
+        let mut expected = TOKEN_LEN;
+        if header.flags.contains(TokenFlags::HAS_EXTENSIONS) {
+            let len_bytes = bytes
+                .get(FIXED_LEN..FIXED_LEN + 2)
+                .ok_or(invalid_length(TOKEN_LEN + 2))?;
+            let ext_len = u16::from_be_bytes([len_bytes[0], len_bytes[1]]) as usize;
+            if ext_len == 0 {
+                return Err(TokenFormatError::MalformedExtensions("empty extension section"));
+            }
+            if ext_len > MAX_EXTENSIONS_LEN {
+                return Err(TokenFormatError::MalformedExtensions("extension section too large"));
+            }
+            expected = TOKEN_LEN + 2 + ext_len;
+            if bytes.len() < expected {
+                return Err(invalid_length(expected));
+            }
+            extensions = ExtensionsRef::parse(&bytes[FIXED_LEN + 2..FIXED_LEN + 2 + ext_len])?;
+        }
+        let signed_len = expected - SIGNATURE_LEN;
+
+        let mut caveats: &[u8] = &[];
+        if header.flags.contains(TokenFlags::ATTENUABLE) {
+            let len_bytes = bytes
+                .get(expected..expected + 2)
+                .ok_or(invalid_length(expected + 2 + CAVEAT_TAG_LEN))?;
+            let caveats_len = u16::from_be_bytes([len_bytes[0], len_bytes[1]]) as usize;
+            if caveats_len > MAX_CAVEATS_LEN {
+                return Err(TokenFormatError::MalformedCaveats("caveat section too large"));
+            }
+            let start = expected + 2;
+            expected = start + caveats_len + CAVEAT_TAG_LEN;
+            if bytes.len() != expected {
+                return Err(invalid_length(expected));
+            }
+            caveats = &bytes[start..start + caveats_len];
+            let mut rest = caveats;
+            while !rest.is_empty() {
+                let (_, _, tail) = split_entry(rest)
+                    .ok_or(TokenFormatError::MalformedCaveats("truncated entry"))?;
+                rest = tail;
+            }
+        }
+        if bytes.len() != expected {
+            return Err(invalid_length(expected));
+        }
+
+        let has = |wanted| extensions.iter().any(|(kind, _)| kind == wanted);
+        if has(ext::CONNECTION_BINDING) != header.flags.contains(TokenFlags::BOUND) {
+            return Err(TokenFormatError::MalformedExtensions("binding flag and entry disagree"));
+        }
+        let delegated = header.flags.contains(TokenFlags::DELEGATED);
+        if has(ext::PARENT_SESSION) != delegated || has(ext::DELEGATION_DEPTH) != delegated {
+            return Err(TokenFormatError::MalformedExtensions("delegation flag and entries disagree"));
+        }
+        Ok(Self { header, bytes, extensions, signed_len, caveats })
+    }
+
+    pub fn header(&self) -> TokenHeader {
+        self.header
+    }
+
+    pub fn session_id(&self) -> &'a [u8; SESSION_ID_LEN] {
+        self.field(SESSION_ID_OFFSET)
+    }
+
+    pub fn context_hash(&self) -> &'a [u8; CONTEXT_HASH_LEN] {
+        self.field(CONTEXT_HASH_OFFSET)
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        u64::from_be_bytes(*self.field(TIMESTAMP_OFFSET))
+    }
+
+    pub fn extensions(&self) -> ExtensionsRef<'a> {
+        self.extensions
+    }
+
+    /// The bytes covered by the signature.
+    pub fn signed_data(&self) -> &'a [u8] {
+        &self.bytes[..self.signed_len]
+    }
+
+    pub fn signature(&self) -> &'a [u8; SIGNATURE_LEN] {
+        self.field(self.signed_len)
+    }
+
+    /// The caveat section of an attenuable token; `None` if the token is not attenuable.
+    pub fn attenuation(&self) -> Option<Attenuation> {
+        if !self.header.flags.contains(TokenFlags::ATTENUABLE) {
+            return None;
+        }
+        let mut rest = self.caveats;
+        let caveats = core::iter::from_fn(|| {
+            let (kind, value, tail) = split_entry(rest)?;
+            rest = tail;
+            Some((kind, value.to_vec()))
+        })
+        .collect();
+        Some(Attenuation { caveats, tag: *self.field(self.bytes.len() - CAVEAT_TAG_LEN) })
+    }
+
+    /// The complete encoded token.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    fn field<const N: usize>(&self, offset: usize) -> &'a [u8; N] {
+        self.bytes[offset..offset + N]
+            .try_into()
+            .expect("token field offsets are within the validated layout")
+    }
+}
@@ -0,0 +1,27 @@
+// src/lib.rs
+//! Offline verification of RTATokens.
+//!
+//! Services that receive RTATokens can check them without calling the RealTimeAuth
+//! server: [`codec`] parses the binary format, and a [`Verifier`] checks the Ed25519
+//! signature against a [`KeySet`] of trusted public keys and the token's freshness.
+//!
+//! What a verifier cannot check offline is left to the server: whether the session
+//! has been revoked or its context has changed, connection bindings, and the caveats
+//! of attenuable tokens, whose chain is keyed with a secret only the server holds.
+//! Attenuable tokens must be introspected.
+//!
+//! The crate is `no_std` + `alloc` when built without the default `std` feature.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod caveat;
+pub mod codec;
+pub mod verify;
+
+pub use caveat::{AccessRequest, Caveat};
+pub use codec::{RTATokenRef, TokenFormatError};
+pub use verify::{Freshness, KeySet, TrustedKey, Verifier, VerifyError};
+
+/// Identifier of a signing key, carried in every token header.
+pub type KeyId = u16;
@@ -0,0 +1,192 @@
+// src/verify.rs
+//! Signature and freshness checks against a caller-supplied key set.
+use alloc::vec::Vec;
+use core::fmt;
+use ring::{hmac, signature};
+use crate::caveat::{self, AccessRequest};
+use crate::codec::{Attenuation, RTATokenRef, TokenFlags, TokenFormatError};
+use crate::KeyId;
+
+/// Length of an Ed25519 public key.
+pub const PUBLIC_KEY_LEN: usize = 32;
+
+/// Reasons a token fails offline verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    Malformed(TokenFormatError),
+    UnknownKey(KeyId),
+    BadSignature,
+    NotYetValid,
+    Expired,
+    /// The token is attenuable. Its caveat chain is keyed with a secret only the
+    /// server holds, so its caveats can only be checked by introspecting it.
+    Attenuable,
+    InvalidCaveatChain,
+    CaveatNotSatisfied(u8),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Malformed(e) => write!(f, "malformed token: {e}"),
+            VerifyError::UnknownKey(id) => write!(f, "unknown signing key {id}"),
+            VerifyError::BadSignature => f.write_str("invalid token signature"),
+            VerifyError::NotYetValid => f.write_str("token issued in the future"),
+            VerifyError::Expired => f.write_str("token expired"),
+            VerifyError::Attenuable => f.write_str("attenuable tokens must be introspected"),
+            VerifyError::InvalidCaveatChain => f.write_str("invalid caveat chain"),
+            VerifyError::CaveatNotSatisfied(kind) => write!(f, "caveat of type {kind:#04x} not satisfied"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VerifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VerifyError::Malformed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<TokenFormatError> for VerifyError {
+    fn from(e: TokenFormatError) -> Self {
+        VerifyError::Malformed(e)
+    }
+}
+
+/// A signing key the verifier trusts, as published in the server's JWK Set.
+#[derive(Debug, Clone)]
+pub struct TrustedKey {
+    pub id: KeyId,
+    pub public_key: [u8; PUBLIC_KEY_LEN],
+}
+
+impl TrustedKey {
+    pub fn new(id: KeyId, public_key: [u8; PUBLIC_KEY_LEN]) -> Self {
+        Self { id, public_key }
+    }
+}
+
+/// The keys a verifier accepts signatures from, looked up by key ID.
+#[derive(Debug, Clone, Default)]
+pub struct KeySet {
+    keys: Vec<TrustedKey>,
+}
+
+impl KeySet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `key`, replacing any key with the same ID.
+    pub fn insert(&mut self, key: TrustedKey) -> &mut Self {
+        self.keys.retain(|k| k.id != key.id);
+        self.keys.push(key);
+        self
+    }
+
+    /// Removes the key `id`, e.g. once the server has retired it.
+    pub fn remove(&mut self, id: KeyId) -> Option<TrustedKey> {
+        let at = self.keys.iter().position(|k| k.id == id)?;
+        Some(self.keys.swap_remove(at))
+    }
+
+    pub fn get(&self, id: KeyId) -> Option<&TrustedKey> {
+        self.keys.iter().find(|k| k.id == id)
+    }
+}
+
+impl FromIterator<TrustedKey> for KeySet {
+    fn from_iter<I: IntoIterator<Item = TrustedKey>>(iter: I) -> Self {
+        let mut set = KeySet::new();
+        for key in iter {
+            set.insert(key);
+        }
+        set
+    }
+}
+
+/// How long tokens stay valid and how far the clocks of issuer and verifier may drift.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Freshness {
+    pub max_age_secs: u64,
+    pub clock_skew_secs: u64,
+}
+
+impl Freshness {
+    /// Unix time at which a token issued at `issued_at` expires, before skew tolerance.
+    pub fn expires_at(&self, issued_at: u64) -> u64 {
+        issued_at.saturating_add(self.max_age_secs)
+    }
+
+    /// Checks a token issued at `issued_at` at unix time `now`, tolerating
+    /// `clock_skew_secs` in either direction.
+    pub fn check(&self, issued_at: u64, now: u64) -> Result<(), VerifyError> {
+        if issued_at > now.saturating_add(self.clock_skew_secs) {
+            return Err(VerifyError::NotYetValid);
+        }
+        if now > self.expires_at(issued_at).saturating_add(self.clock_skew_secs) {
+            return Err(VerifyError::Expired);
+        }
+        Ok(())
+    }
+}
+
+/// Verifies the Ed25519 `sig` over `data` with `public_key`.
+pub fn verify_signature(public_key: &[u8; PUBLIC_KEY_LEN], data: &[u8], sig: &[u8]) -> Result<(), VerifyError> {
+    signature::UnparsedPublicKey::new(&signature::ED25519, public_key)
+        .verify(data, sig)
+        .map_err(|_| VerifyError::BadSignature)
+}
+
+/// Recomputes the caveat chain of a token with `signature` from `root` and evaluates
+/// every caveat against `request` at unix time `now`.
+///
+/// Only the server holds `root`: whoever has it could re-tag a token with caveats
+/// removed.
+pub fn check_caveats(
+    root: &hmac::Key,
+    signature: &[u8],
+    attenuation: &Attenuation,
+    request: &AccessRequest<'_>,
+    now: u64,
+) -> Result<(), VerifyError> {
+    if !caveat::verify_chain(root, signature, attenuation.caveats(), attenuation.tag()) {
+        return Err(VerifyError::InvalidCaveatChain);
+    }
+    caveat::evaluate(attenuation.caveats(), request, now)
+}
+
+/// Verifies encoded tokens against a [`KeySet`].
+#[derive(Debug, Clone)]
+pub struct Verifier<'k> {
+    keys: &'k KeySet,
+    freshness: Freshness,
+}
+
+impl<'k> Verifier<'k> {
+    pub fn new(keys: &'k KeySet, freshness: Freshness) -> Self {
+        Self { keys, freshness }
+    }
+
+    /// Parses `bytes` and checks the signature and the token's freshness at unix time
+    /// `now`.
+    ///
+    /// On success the parsed token is returned for reading its claims. The context
+    /// hash and any connection binding are not checked; they can only be verified by
+    /// the server. Attenuable tokens are rejected with [`VerifyError::Attenuable`]:
+    /// their caveats can only be checked by introspection.
+    pub fn verify<'a>(&self, bytes: &'a [u8], now: u64) -> Result<RTATokenRef<'a>, VerifyError> {
+        let token = RTATokenRef::parse(bytes)?;
+        let key_id = token.header().key_id;
+        let key = self.keys.get(key_id).ok_or(VerifyError::UnknownKey(key_id))?;
+        verify_signature(&key.public_key, token.signed_data(), token.signature())?;
+        self.freshness.check(token.timestamp(), now)?;
+        if token.header().flags.contains(TokenFlags::ATTENUABLE) {
+            return Err(VerifyError::Attenuable);
+        }
+        Ok(token)
+    }
+}
@@ -0,0 +1,325 @@
+// tests/roundtrip.rs
+//! Property tests for the token codec: well-formed tokens survive encoding and
+//! decoding byte for byte, arbitrary input is rejected without panicking, and
+//! signatures and caveat chains only verify when left untouched.
+use proptest::collection::vec;
+use proptest::prelude::*;
+use ring::hmac;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use rta_verify::caveat::{self, AccessRequest, Caveat};
+use rta_verify::codec::{
+    self, ext, Attenuation, Extensions, RTATokenRef, SignedFields, TokenFlags, TokenHeader,
+    TokenVersion, CAVEAT_TAG_LEN, MAX_TOKEN_LEN, SIGNATURE_LEN,
+};
+use rta_verify::verify::check_caveats;
+use rta_verify::{Freshness, KeySet, TrustedKey, Verifier, VerifyError};
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+
+/// `(type, value)` TLV entries.
+type Entries = Vec<(u8, Vec<u8>)>;
+
+/// Everything needed to encode one token.
+#[derive(Debug, Clone)]
+struct TokenParts {
+    version: TokenVersion,
+    key_id: u16,
+    session_id: [u8; 16],
+    context_hash: [u8; 32],
+    timestamp: u64,
+    /// Parent session and depth of a delegated token.
+    delegation: Option<([u8; 16], u8)>,
+    extensions: Entries,
+    binding: Option<[u8; 32]>,
+    signature: [u8; SIGNATURE_LEN],
+    /// Caveats and final tag of an attenuable token.
+    attenuation: Option<(Entries, [u8; CAVEAT_TAG_LEN])>,
+}
+
+impl TokenParts {
+    /// Extensions as the issuer would build them; non-repeatable types keep their first entry.
+    fn extensions(&self) -> Extensions {
+        let mut seen = BTreeSet::new();
+        let mut exts = Extensions::new();
+        for (kind, value) in &self.extensions {
+            if *kind == ext::SCOPE || seen.insert(*kind) {
+                exts.push(*kind, value.clone());
+            }
+        }
+        if let Some(binding) = self.binding {
+            exts.push(ext::CONNECTION_BINDING, binding);
+        }
+        if let Some((parent, depth)) = self.delegation {
+            exts.push(ext::PARENT_SESSION, parent).push(ext::DELEGATION_DEPTH, [depth]);
+        }
+        exts
+    }
+
+    fn header(&self, extensions: &Extensions) -> TokenHeader {
+        let mut flags = TokenFlags::empty();
+        if self.version == TokenVersion::V2 {
+            if !extensions.is_empty() {
+                flags = flags.union(TokenFlags::HAS_EXTENSIONS);
+            }
+            if self.binding.is_some() {
+                flags = flags.union(TokenFlags::BOUND);
+            }
+            if self.delegation.is_some() {
+                flags = flags.union(TokenFlags::DELEGATED);
+            }
+            if self.attenuation.is_some() {
+                flags = flags.union(TokenFlags::ATTENUABLE);
+            }
+        }
+        TokenHeader { version: self.version, key_id: self.key_id, flags }
+    }
+
+    fn signed(&self) -> Vec<u8> {
+        let extensions = self.extensions();
+        codec::encode_signed(&SignedFields {
+            header: self.header(&extensions),
+            session_id: &self.session_id,
+            context_hash: &self.context_hash,
+            timestamp: self.timestamp,
+            extensions: &extensions,
+        })
+        .expect("generated extensions fit the section")
+    }
+
+    fn attenuation(&self) -> Option<Attenuation> {
+        let (caveats, tag) = self.attenuation.as_ref()?;
+        let mut attenuation = Attenuation::new(*tag);
+        for (kind, value) in caveats {
+            attenuation.push(*kind, value.clone(), *tag).expect("generated caveats fit the section");
+        }
+        Some(attenuation)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        codec::encode(&self.signed(), &self.signature, self.attenuation().as_ref())
+    }
+}
+
+fn extension() -> impl Strategy<Value = (u8, Vec<u8>)> {
+    let text_kind = prop::sample::select(vec![ext::AGENT_ID, ext::SUBJECT, ext::PROVIDER, ext::AUDIENCE, ext::SCOPE]);
+    prop_oneof![
+        (text_kind, "\\PC{0,24}").prop_map(|(kind, value)| (kind, value.into_bytes())),
+        any::<u32>().prop_map(|g| (ext::GENERATION, g.to_be_bytes().to_vec())),
+        // Types this codec does not know are carried through unchanged.
+        (0x0au8.., vec(any::<u8>(), 0..32)),
+    ]
+}
+
+fn caveat_entry() -> impl Strategy<Value = (u8, Vec<u8>)> {
+    (any::<u8>(), vec(any::<u8>(), 0..32))
+}
+
+fn token_parts() -> impl Strategy<Value = TokenParts> {
+    let v1 = (any::<u16>(), any::<[u8; 16]>(), any::<[u8; 32]>(), any::<u64>(), any::<[u8; 32]>(), any::<[u8; 32]>())
+        .prop_map(|(key_id, session_id, context_hash, timestamp, sig_a, sig_b)| TokenParts {
+            version: TokenVersion::V1,
+            key_id,
+            session_id,
+            context_hash,
+            timestamp,
+            delegation: None,
+            extensions: Vec::new(),
+            binding: None,
+            signature: signature(sig_a, sig_b),
+            attenuation: None,
+        });
+    let v2 = (
+        (any::<u16>(), any::<[u8; 16]>(), any::<[u8; 32]>(), any::<u64>(), any::<Option<([u8; 16], u8)>>()),
+        (vec(extension(), 0..12), any::<Option<[u8; 32]>>()),
+        (any::<[u8; 32]>(), any::<[u8; 32]>()),
+        prop::option::of((vec(caveat_entry(), 0..8), any::<[u8; CAVEAT_TAG_LEN]>())),
+    )
+        .prop_map(|((key_id, session_id, context_hash, timestamp, delegation), (extensions, binding), (sig_a, sig_b), attenuation)| {
+            TokenParts {
+                version: TokenVersion::V2,
+                key_id,
+                session_id,
+                context_hash,
+                timestamp,
+                delegation,
+                extensions,
+                binding,
+                signature: signature(sig_a, sig_b),
+                attenuation,
+            }
+        });
+    prop_oneof![1 => v1, 4 => v2]
+}
+
+fn signature(a: [u8; 32], b: [u8; 32]) -> [u8; SIGNATURE_LEN] {
+    let mut sig = [0u8; SIGNATURE_LEN];
+    sig[..32].copy_from_slice(&a);
+    sig[32..].copy_from_slice(&b);
+    sig
+}
+
+fn caveat() -> impl Strategy<Value = Caveat> {
+    prop_oneof![
+        "\\PC{0,24}".prop_map(Caveat::ResourcePrefix),
+        vec("[a-z:_]{1,12}", 1..4).prop_map(Caveat::Actions),
+        any::<u64>().prop_map(Caveat::ExpiresAt),
+        (any::<[u8; 4]>(), 0u8..=32).prop_map(|(a, p)| Caveat::IpRange { network: IpAddr::from(a), prefix_len: p }),
+        (any::<[u8; 16]>(), 0u8..=128).prop_map(|(a, p)| Caveat::IpRange { network: IpAddr::from(a), prefix_len: p }),
+    ]
+}
+
+/// Re-encodes a parsed token from its decoded fields, not its signed bytes, so that
+/// anything the decoder accepts must be the canonical encoding of what it decoded.
+fn reencode(token: &RTATokenRef<'_>) -> Vec<u8> {
+    let signed = codec::encode_signed(&SignedFields {
+        header: token.header(),
+        session_id: token.session_id(),
+        context_hash: token.context_hash(),
+        timestamp: token.timestamp(),
+        extensions: &Extensions::from(token.extensions()),
+    })
+    .expect("parsed extensions fit the section");
+    codec::encode(&signed, token.signature(), token.attenuation().as_ref())
+}
+
+proptest! {
+    #[test]
+    fn tokens_round_trip(parts in token_parts()) {
+        let bytes = parts.encode();
+        prop_assert!(bytes.len() <= MAX_TOKEN_LEN);
+        let token = RTATokenRef::parse(&bytes).expect("well-formed tokens parse");
+
+        let extensions = parts.extensions();
+        prop_assert_eq!(token.header(), parts.header(&extensions));
+        prop_assert_eq!(token.session_id(), &parts.session_id);
+        prop_assert_eq!(token.context_hash(), &parts.context_hash);
+        prop_assert_eq!(token.timestamp(), parts.timestamp);
+        prop_assert_eq!(token.signature(), &parts.signature);
+        prop_assert_eq!(Extensions::from(token.extensions()), extensions);
+        prop_assert_eq!(token.attenuation(), parts.attenuation());
+        prop_assert_eq!(reencode(&token), bytes);
+    }
+
+    #[test]
+    fn truncated_and_extended_tokens_are_rejected(parts in token_parts(), cut in any::<prop::sample::Index>(), extra in vec(any::<u8>(), 1..16)) {
+        let bytes = parts.encode();
+        let truncated = &bytes[..cut.index(bytes.len())];
+        prop_assert!(RTATokenRef::parse(truncated).is_err());
+
+        let mut extended = bytes.clone();
+        extended.extend_from_slice(&extra);
+        prop_assert!(RTATokenRef::parse(&extended).is_err());
+    }
+
+    #[test]
+    fn arbitrary_input_parses_canonically_or_not_at_all(bytes in vec(any::<u8>(), 0..2 * MAX_TOKEN_LEN)) {
+        if let Ok(token) = RTATokenRef::parse(&bytes) {
+            prop_assert_eq!(reencode(&token), bytes);
+        }
+    }
+
+    #[test]
+    fn mutated_tokens_parse_canonically_or_not_at_all(parts in token_parts(), at in any::<prop::sample::Index>(), byte in any::<u8>()) {
+        let mut bytes = parts.encode();
+        let at = at.index(bytes.len());
+        bytes[at] = byte;
+        if let Ok(token) = RTATokenRef::parse(&bytes) {
+            prop_assert_eq!(reencode(&token), bytes);
+        }
+    }
+
+    #[test]
+    fn caveats_round_trip(caveat in caveat()) {
+        let (kind, value) = caveat.encode();
+        prop_assert_eq!(Caveat::decode(kind, &value), Some(caveat));
+    }
+
+    #[test]
+    fn caveat_chains_verify_only_unmodified(
+        root in any::<[u8; 32]>(),
+        other_root in any::<[u8; 32]>(),
+        sig in any::<[u8; 32]>(),
+        caveats in vec(caveat_entry(), 1..8),
+        drop in any::<prop::sample::Index>(),
+    ) {
+        let root_key = hmac::Key::new(hmac::HMAC_SHA256, &root);
+        let mut tag = caveat::initial_tag(&root_key, &sig);
+        for (kind, value) in &caveats {
+            tag = caveat::chain_tag(&tag, *kind, value);
+        }
+        let entries = || caveats.iter().map(|(k, v)| (*k, v.as_slice()));
+        prop_assert!(caveat::verify_chain(&root_key, &sig, entries(), &tag));
+
+        let mut dropped = caveats.clone();
+        dropped.remove(drop.index(caveats.len()));
+        prop_assert!(!caveat::verify_chain(&root_key, &sig, dropped.iter().map(|(k, v)| (*k, v.as_slice())), &tag));
+
+        prop_assume!(root != other_root);
+        let other_key = hmac::Key::new(hmac::HMAC_SHA256, &other_root);
+        prop_assert!(!caveat::verify_chain(&other_key, &sig, entries(), &tag));
+    }
+
+    #[test]
+    fn signed_tokens_verify_only_unmodified(seed in any::<[u8; 32]>(), parts in token_parts(), at in any::<prop::sample::Index>(), flip in 1u8..) {
+        let key = Ed25519KeyPair::from_seed_unchecked(&seed).expect("any 32-byte seed is a valid key");
+        let mut parts = TokenParts { attenuation: None, ..parts };
+        let signed = parts.signed();
+        parts.signature.copy_from_slice(key.sign(&signed).as_ref());
+        let bytes = parts.encode();
+
+        let public_key: [u8; 32] = key.public_key().as_ref().try_into().unwrap();
+        let keys: KeySet = [TrustedKey::new(parts.key_id, public_key)].into_iter().collect();
+        let verifier = Verifier::new(&keys, Freshness { max_age_secs: 300, clock_skew_secs: 30 });
+        prop_assert!(verifier.verify(&bytes, parts.timestamp).is_ok());
+
+        let mut tampered = bytes.clone();
+        tampered[at.index(bytes.len())] ^= flip;
+        prop_assert!(verifier.verify(&tampered, parts.timestamp).is_err());
+    }
+}
+
+#[test]
+fn attenuable_tokens_are_left_to_introspection() {
+    let key = Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+    let root = hmac::Key::new(hmac::HMAC_SHA256, &[9; 32]);
+    let mut parts = TokenParts {
+        version: TokenVersion::V2,
+        key_id: 1,
+        session_id: [1; 16],
+        context_hash: [2; 32],
+        timestamp: 1_700_000_000,
+        delegation: None,
+        extensions: Vec::new(),
+        binding: None,
+        signature: [0; SIGNATURE_LEN],
+        attenuation: Some((Vec::new(), [0; CAVEAT_TAG_LEN])),
+    };
+    let signed = parts.signed();
+    parts.signature.copy_from_slice(key.sign(&signed).as_ref());
+    let caveat = Caveat::ResourcePrefix("/reports/".into());
+    let (kind, value) = caveat.encode();
+    let tag = caveat::initial_tag(&root, &parts.signature);
+    parts.attenuation = Some((vec![(kind, value.clone())], caveat::chain_tag(&tag, kind, &value)));
+    let bytes = parts.encode();
+
+    let public_key: [u8; 32] = key.public_key().as_ref().try_into().unwrap();
+    let keys: KeySet = [TrustedKey::new(1, public_key)].into_iter().collect();
+    let verifier = Verifier::new(&keys, Freshness { max_age_secs: 300, clock_skew_secs: 30 });
+    assert_eq!(verifier.verify(&bytes, parts.timestamp).err(), Some(VerifyError::Attenuable));
+    // A forged signature is still reported as such.
+    let mut forged = bytes.clone();
+    forged[codec::FIXED_LEN] ^= 1;
+    assert_eq!(verifier.verify(&forged, parts.timestamp).err(), Some(VerifyError::BadSignature));
+
+    // The server, holding the root, checks the chain and every caveat.
+    let token = RTATokenRef::parse(&bytes).unwrap();
+    let attenuation = token.attenuation().unwrap();
+    let allowed = AccessRequest { resource: Some("/reports/q3"), ..AccessRequest::default() };
+    let denied = AccessRequest { resource: Some("/payroll"), ..AccessRequest::default() };
+    let now = parts.timestamp;
+    assert_eq!(check_caveats(&root, token.signature(), &attenuation, &allowed, now), Ok(()));
+    assert_eq!(
+        check_caveats(&root, token.signature(), &attenuation, &denied, now),
+        Err(VerifyError::CaveatNotSatisfied(kind)),
+    );
+}
@@ -0,0 +1,172 @@
+// tests/vectors.rs
+//! Checks the published test vectors in `test-vectors/` against the codec and
+//! verifier: every valid vector decodes to its documented fields and verifies under
+//! the documented key, and every invalid vector is rejected. Attenuable vectors are
+//! left to introspection by the verifier; their caveat chains are checked as the
+//! server does, with the vectors' caveat root.
+use ring::digest::{digest, SHA256};
+use ring::hmac;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use rta_verify::caveat;
+use rta_verify::codec::{RTATokenRef, TokenFlags, TokenVersion};
+use rta_verify::verify;
+use rta_verify::{AccessRequest, Freshness, KeySet, TrustedKey, Verifier, VerifyError};
+use serde_json::Value;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Vectors carry fixed timestamps; freshness is not what they test.
+const ANY_AGE: Freshness = Freshness { max_age_secs: u64::MAX, clock_skew_secs: 0 };
+
+fn load(name: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test-vectors").join(name);
+    let json = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    serde_json::from_str(&json).unwrap()
+}
+
+fn bytes(value: &Value) -> Vec<u8> {
+    hex::decode(value.as_str().expect("hex string")).expect("valid hex")
+}
+
+fn uint(value: &Value) -> u64 {
+    value.as_u64().expect("unsigned integer")
+}
+
+/// The `value` (UTF-8) or `value_hex` of an extension or caveat entry.
+fn entry_value(entry: &Value) -> Vec<u8> {
+    match entry.get("value") {
+        Some(value) => value.as_str().unwrap().as_bytes().to_vec(),
+        None => bytes(&entry["value_hex"]),
+    }
+}
+
+fn access_request(request: &Value) -> AccessRequest<'_> {
+    AccessRequest {
+        resource: request["resource"].as_str(),
+        action: request["action"].as_str(),
+        client_ip: request["client_ip"].as_str().map(|ip| ip.parse::<IpAddr>().unwrap()),
+    }
+}
+
+/// The caveat root the server used for a vector file's attenuable vectors.
+fn caveat_root(vectors: &Value) -> hmac::Key {
+    let root = vectors["signing_key"].get("caveat_root").expect("vectors publish a caveat root");
+    hmac::Key::new(hmac::HMAC_SHA256, &bytes(root))
+}
+
+/// Builds the key set of a vector file, checking the public key matches the seed.
+fn key_set(vectors: &Value, key_ids: impl Iterator<Item = u16>) -> KeySet {
+    let signing_key = &vectors["signing_key"];
+    let key_pair = Ed25519KeyPair::from_seed_unchecked(&bytes(&signing_key["seed"])).unwrap();
+    let public_key: [u8; 32] = bytes(&signing_key["public_key"]).try_into().unwrap();
+    assert_eq!(key_pair.public_key().as_ref(), public_key);
+    key_ids.map(|id| TrustedKey::new(id, public_key)).collect()
+}
+
+/// Checks the caveats of an attenuable vector, the tag after each of them and the
+/// vector's requests against them.
+fn check_caveats(vector: &Value, token: &RTATokenRef<'_>, root: &hmac::Key) {
+    let attenuation = token.attenuation().expect("attenuable vector");
+    let mut tag = caveat::initial_tag(root, token.signature());
+    assert_eq!(tag.as_slice(), bytes(&vector["initial_tag"]));
+
+    let expected = vector["caveats"].as_array().unwrap();
+    let decoded: Vec<(u8, &[u8])> = attenuation.caveats().collect();
+    assert_eq!(decoded.len(), expected.len());
+    for ((kind, value), expected) in decoded.into_iter().zip(expected) {
+        assert_eq!(u64::from(kind), uint(&expected["type"]));
+        assert_eq!(value, entry_value(expected));
+        tag = caveat::chain_tag(&tag, kind, value);
+        assert_eq!(tag.as_slice(), bytes(&expected["tag"]));
+    }
+    assert_eq!(attenuation.tag(), &tag);
+
+    let timestamp = token.timestamp();
+    let request = vector.get("request").map(access_request).unwrap_or_default();
+    verify::check_caveats(root, token.signature(), &attenuation, &request, timestamp)
+        .unwrap_or_else(|e| panic!("caveats of {} not satisfied: {}", vector, e));
+    if let Some(denied) = vector.get("denied_request") {
+        let failed = verify::check_caveats(root, token.signature(), &attenuation, &access_request(denied), timestamp);
+        assert_eq!(failed.err(), Some(VerifyError::CaveatNotSatisfied(uint(&denied["caveat"]) as u8)));
+    }
+}
+
+fn check_vectors(name: &str, version: TokenVersion) {
+    let vectors = load(name);
+    let valid = vectors["valid"].as_array().unwrap();
+    let invalid = vectors["invalid"].as_array().unwrap();
+    assert!(!valid.is_empty() && !invalid.is_empty(), "{} has no vectors", name);
+
+    let key_ids = valid.iter().map(|v| uint(&v["key_id"]) as u16);
+    let keys = key_set(&vectors, key_ids.chain([1]));
+    let verifier = Verifier::new(&keys, ANY_AGE);
+
+    for vector in valid {
+        let encoded = bytes(&vector["token"]);
+        let timestamp = uint(&vector["timestamp"]);
+        let attenuable = vector.get("caveats").is_some();
+        let token = match verifier.verify(&encoded, timestamp) {
+            Ok(token) if !attenuable => token,
+            // Rejected only after the signature and freshness checks passed.
+            Err(VerifyError::Attenuable) if attenuable => RTATokenRef::parse(&encoded).unwrap(),
+            other => panic!("{}: valid vector {} verified as {:?}", name, vector, other.err()),
+        };
+
+        let header = token.header();
+        assert_eq!(header.version, version);
+        assert_eq!(u64::from(header.key_id), uint(&vector["key_id"]));
+        assert_eq!(u64::from(header.flags.bits()), vector.get("flags").map_or(0, uint));
+        assert_eq!(token.session_id().as_slice(), bytes(&vector["session_id"]));
+        let context = vector["context"].as_str().unwrap();
+        assert_eq!(token.context_hash().as_slice(), digest(&SHA256, context.as_bytes()).as_ref());
+        assert_eq!(token.context_hash().as_slice(), bytes(&vector["context_hash"]));
+        assert_eq!(token.timestamp(), timestamp);
+        assert_eq!(token.signed_data(), bytes(&vector["signed_data"]));
+
+        let expected: Vec<(u8, Vec<u8>)> = vector.get("extensions")
+            .and_then(Value::as_array)
+            .map(|entries| entries.iter()
+                .map(|e| (uint(&e["type"]) as u8, entry_value(e)))
+                .collect())
+            .unwrap_or_default();
+        let decoded: Vec<(u8, Vec<u8>)> = token.extensions().iter().map(|(k, v)| (k, v.to_vec())).collect();
+        assert_eq!(decoded, expected);
+        assert_eq!(header.flags.contains(TokenFlags::HAS_EXTENSIONS), !expected.is_empty());
+        assert_eq!(
+            header.flags.contains(TokenFlags::BOUND),
+            expected.iter().any(|(kind, _)| *kind == rta_verify::codec::ext::CONNECTION_BINDING),
+        );
+
+        assert_eq!(header.flags.contains(TokenFlags::ATTENUABLE), attenuable);
+        if attenuable {
+            check_caveats(vector, &token, &caveat_root(&vectors));
+        }
+    }
+
+    for vector in invalid {
+        let encoded = bytes(&vector["token"]);
+        let rejected = match RTATokenRef::parse(&encoded) {
+            Err(_) => true,
+            Ok(token) => match verifier.verify(&encoded, token.timestamp()) {
+                // As the server sees it: a caveat chain that does not verify.
+                Err(VerifyError::Attenuable) => {
+                    let attenuation = token.attenuation().expect("attenuable token");
+                    let root = caveat_root(&vectors);
+                    !caveat::verify_chain(&root, token.signature(), attenuation.caveats(), attenuation.tag())
+                }
+                verified => verified.is_err(),
+            },
+        };
+        assert!(rejected, "{}: invalid vector accepted ({})", name, vector["reason"]);
+    }
+}
+
+#[test]
+fn v1_vectors() {
+    check_vectors("rtatoken-v1.json", TokenVersion::V1);
+}
+
+#[test]
+fn v2_vectors() {
+    check_vectors("rtatoken-v2.json", TokenVersion::V2);
+}
@@ -1,42 +1,358 @@
 // src/application/commands.rs
 use anyhow::Result;
 use ring::rand::{self, SecureRandom};
-use crate::domain::token::RTAToken;
+use thiserror::Error;
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::sync::atomic::Ordering;
+use crate::application::queries::validate_session_token;
+use crate::application::state::AppState;
+use crate::domain::binding::{binding_hash, BindingEvidence, BINDING_LEN};
+use crate::domain::clock::unix_now;
+use crate::domain::context::{narrow_scopes, AuthContext, DevicePosture, RiskLevel};
+use rta_verify::caveat::AccessRequest;
+use crate::domain::delegation;
+use crate::domain::jwt::RtaJwtClaims;
+use crate::domain::session::Session;
+use crate::domain::token::{RTAToken, TokenError};
+use rta_verify::codec::{ext, Extensions};
 use crate::domain::events::DomainEvent;
 use crate::infrastructure::idp_adapter;
-use crate::config::IdpProviders;
+
+/// The identity provider reported the OAuth token presented for exchange as inactive
+/// or rejected it.
+#[derive(Debug, Error)]
+#[error("OAuth token rejected by the identity provider")]
+pub struct OAuthTokenRejected;
 
 pub struct IssueTokenCommand {
     pub oauth_token: String,
     pub agent_id: String,
     pub provider: Option<String>,
+    /// Scopes for the token, a subset of those the identity provider granted; `None`
+    /// takes them all.
+    pub scopes: Option<BTreeSet<String>>,
+    /// Address the exchange request arrived from.
+    pub client_network: Option<IpAddr>,
+    /// Posture reported by the agent.
+    pub device_posture: DevicePosture,
+    /// Service the token is intended for, embedded in the token when present.
+    pub audience: Option<String>,
+    /// Keying material exported from the requesting connection; binds the session's
+    /// tokens to that connection.
+    pub connection_binding: Option<[u8; BINDING_LEN]>,
+    /// Issue tokens that holders can narrow with caveats.
+    pub attenuable: bool,
+}
+
+/// Requests a token issued on behalf of the holder of `parent_token`.
+pub struct DelegateTokenCommand {
+    /// The delegating holder's token; must be valid for its session.
+    pub parent_token: RTAToken,
+    /// Proof that the requester holds `parent_token` if it is connection-bound.
+    pub parent_binding: Option<BindingEvidence>,
+    /// Agent the delegated token is issued to.
+    pub agent_id: String,
+    /// Scopes for the child, a subset of the parent's; `None` inherits them all.
+    pub scopes: Option<BTreeSet<String>>,
+    /// How many further levels the child itself may delegate.
+    pub max_depth: u8,
+    pub client_network: Option<IpAddr>,
+    pub device_posture: DevicePosture,
+    pub audience: Option<String>,
+    pub attenuable: bool,
+}
+
+/// Requests a JWT mirror of a presented RTAToken for a legacy backend.
+pub struct TranslateTokenCommand {
+    pub token: RTAToken,
+    /// Proof that the requester holds `token` if it is connection-bound.
+    pub binding: Option<BindingEvidence>,
+    /// Backend the JWT is minted for; must match the token's audience, if it has one.
+    pub audience: String,
+    /// Scopes for the JWT, a subset of the token's; `None` mirrors them all.
+    pub scopes: Option<BTreeSet<String>>,
+}
+
+/// A JWT minted from an RTAToken.
+pub struct MintedJwt {
+    pub jwt: String,
+    /// Unix time at which the JWT expires.
+    pub expires_at: u64,
+    /// Space-delimited scopes the JWT carries.
+    pub scope: String,
+}
+
+/// Re-issues the token of an existing session after its context changed.
+pub struct RefreshTokenCommand {
+    pub session_id: [u8; 16],
+    /// The session's new context.
+    pub context: AuthContext,
 }
 
 pub struct RevokeTokenCommand {
     pub session_id: String,
 }
 
-pub async fn handle_issue_token(cmd: IssueTokenCommand, context_data: &[u8], idp_providers: &IdpProviders) -> Result<(RTAToken, DomainEvent)> {
+/// Applies a change to a session's context observed by an external source, such as a
+/// raised risk level or a device found non-compliant.
+pub struct ChangeContextCommand {
+    pub session_id: [u8; 16],
+    pub risk_level: Option<RiskLevel>,
+    pub device_posture: Option<DevicePosture>,
+}
+
+/// Asks the holder of a session to authenticate again with the identity provider.
+pub struct RequireStepUpCommand {
+    pub session_id: [u8; 16],
+    pub reason: Option<String>,
+}
+
+/// Activates a new policy set.
+pub struct UpdatePolicyCommand {
+    pub policy_version: u32,
+}
+
+pub async fn handle_issue_token(cmd: IssueTokenCommand, state: &AppState) -> Result<(RTAToken, DomainEvent)> {
     // Validate the OAuth token via IdP introspection using the selected provider.
     let prov = cmd.provider.as_deref();
-    let valid = idp_adapter::introspect(&cmd.oauth_token, prov, idp_providers).await?;
-    if !valid {
-        return Err(anyhow::anyhow!("OAuth token introspection failed"));
-    }
+    let introspection = idp_adapter::introspect(&cmd.oauth_token, prov, &state.idp_providers).await?
+        .ok_or(OAuthTokenRejected)?;
+
+    // Build the context the token is bound to from the introspected claims and the request.
+    let context = AuthContext {
+        // Providers that do not report a subject leave it empty rather than guessing one.
+        subject: introspection.subject.unwrap_or_default(),
+        agent_id: cmd.agent_id,
+        provider: introspection.provider,
+        scopes: narrow_scopes(introspection.scopes.into_iter().collect(), cmd.scopes)?,
+        risk_level: RiskLevel::default(),
+        client_network: cmd.client_network,
+        device_posture: cmd.device_posture,
+        policy_version: state.policy_version(),
+    };
 
     // Generate a random 16-byte session ID.
     let mut session_id = [0u8; 16];
     let rng = rand::SystemRandom::new();
     rng.fill(&mut session_id)?;
 
-    // Issue the RTAToken.
-    let token = RTAToken::issue(session_id, context_data)?;
+    // Issue the RTAToken, signed with the keyring's active key, and record the session.
+    let mut session = Session::new(session_id, context, cmd.audience, cmd.connection_binding);
+    session.max_depth = state.token_config.max_delegation_depth;
+    session.attenuable = cmd.attenuable;
+    let token = issue_for_session(&session, state)?;
+    session.issued_at = token.timestamp();
+    state.sessions.insert(session);
     let session_id_hex = hex::encode(session_id);
     let event = DomainEvent::TokenIssued { session_id: session_id_hex };
     Ok((token, event))
 }
 
-pub async fn handle_revoke_token(cmd: RevokeTokenCommand) -> Result<DomainEvent> {
-    // Domain logic to revoke a token goes here.
-    Ok(DomainEvent::TokenRevoked { session_id: cmd.session_id })
+/// Issues a child token on behalf of the holder of a valid parent token.
+///
+/// The child gets its own session, recorded under the parent's so that revoking the
+/// parent revokes it too. It inherits the parent's subject and provider, may only
+/// narrow the parent's scopes, and can never extend the chain beyond the parent's
+/// depth limit.
+///
+/// Child tokens are not bound to a connection: the delegator hands them to an agent
+/// on a connection of its own, whose keying material the delegator cannot prove.
+/// They are bearer tokens for as long as they live, so delegators should narrow them
+/// to what the agent needs. The delegator's connection holds the child's session:
+/// it refreshes the child's tokens and is pushed its revocation.
+pub async fn handle_delegate_token(cmd: DelegateTokenCommand, state: &AppState) -> Result<(RTAToken, DomainEvent)> {
+    delegation::check_parent_token(&cmd.parent_token)?;
+    let parent = validate_session_token(
+        &cmd.parent_token,
+        state,
+        cmd.parent_binding.as_ref(),
+        &AccessRequest::default(),
+    )?;
+    let max_depth = delegation::child_max_depth(&parent, cmd.max_depth)?;
+    let scopes = delegation::attenuate_scopes(&parent, cmd.scopes)?;
+
+    let context = AuthContext {
+        agent_id: cmd.agent_id,
+        scopes,
+        client_network: cmd.client_network,
+        device_posture: cmd.device_posture,
+        ..parent.context.clone()
+    };
+
+    let mut session_id = [0u8; 16];
+    let rng = rand::SystemRandom::new();
+    rng.fill(&mut session_id)?;
+
+    let mut session = Session::new(session_id, context, cmd.audience, None);
+    session.parent = Some(parent.session_id);
+    session.depth = parent.depth + 1;
+    session.max_depth = max_depth;
+    session.attenuable = cmd.attenuable;
+    let token = issue_for_session(&session, state)?;
+    session.issued_at = token.timestamp();
+    let depth = session.depth;
+    if !state.sessions.insert_child(session) {
+        // The parent was revoked after it was validated above.
+        return Err(TokenError::Revoked.into());
+    }
+    let event = DomainEvent::TokenDelegated {
+        session_id: hex::encode(session_id),
+        parent_session_id: hex::encode(parent.session_id),
+        depth,
+    };
+    Ok((token, event))
+}
+
+/// Mints a JWT for `cmd.audience` from a valid RTAToken, signed with the keyring.
+pub async fn handle_translate_token(cmd: TranslateTokenCommand, state: &AppState) -> Result<MintedJwt> {
+    validate_session_token(&cmd.token, state, cmd.binding.as_ref(), &AccessRequest::default())?;
+    if cmd.token.audience().is_some_and(|aud| aud != cmd.audience) {
+        return Err(TokenError::AudienceMismatch.into());
+    }
+    let scopes = narrow_scopes(cmd.token.scopes().map(str::to_owned).collect(), cmd.scopes)?;
+    mint_jwt(&cmd.token, Some(&cmd.audience), Some(&scopes), state)
+}
+
+/// Signs a short-lived JWT mirroring `token` with the keyring's active key. `scopes`,
+/// a subset of the token's, replaces its scopes in the JWT.
+pub fn mint_jwt(
+    token: &RTAToken,
+    audience: Option<&str>,
+    scopes: Option<&BTreeSet<String>>,
+    state: &AppState,
+) -> Result<MintedJwt> {
+    let now = unix_now();
+    let mut claims = RtaJwtClaims::mirror(
+        token,
+        &state.jwt_config.issuer,
+        audience,
+        now,
+        state.jwt_config.ttl_secs,
+        token.expires_at(&state.token_config),
+    );
+    if let Some(scopes) = scopes {
+        claims.scope = scopes.iter().map(String::as_str).collect::<Vec<_>>().join(" ");
+    }
+    let jwt = state.keyring.sign_jwt(&claims)?;
+    Ok(MintedJwt { jwt, expires_at: claims.exp, scope: claims.scope })
+}
+
+/// Re-issues a session's token bound to its new context.
+///
+/// The session ID is preserved and the generation incremented, which immediately
+/// invalidates every token of an earlier generation.
+pub async fn handle_refresh_token(cmd: RefreshTokenCommand, state: &AppState) -> Result<(RTAToken, DomainEvent)> {
+    let now = unix_now();
+    let session = state.sessions.advance(&cmd.session_id, cmd.context, now)
+        .ok_or_else(|| anyhow::anyhow!("Unknown or revoked session"))?;
+    let token = issue_for_session(&session, state)?;
+    let event = DomainEvent::TokenRefreshed {
+        session_id: hex::encode(session.session_id),
+        generation: session.generation,
+    };
+    Ok((token, event))
+}
+
+/// Issues the token for the session's current context and generation.
+fn issue_for_session(session: &Session, state: &AppState) -> Result<RTAToken> {
+    RTAToken::issue(
+        &state.keyring,
+        session.session_id,
+        &session.context,
+        claims_extensions(session),
+        session.attenuable,
+    )
+}
+
+/// Agent and subject claims embedded in issued tokens so that backends and audit logs
+/// can attribute every action to a specific agent.
+fn claims_extensions(session: &Session) -> Extensions {
+    let context = &session.context;
+    let mut exts = Extensions::new();
+    exts.push(ext::AGENT_ID, context.agent_id.as_bytes())
+        .push(ext::PROVIDER, context.provider.as_bytes())
+        .push_opt(ext::AUDIENCE, session.audience.as_deref())
+        .push(ext::GENERATION, session.generation.to_be_bytes());
+    if let Some(secret) = &session.binding_secret {
+        exts.push(ext::CONNECTION_BINDING, binding_hash(secret));
+    }
+    if let Some(parent) = session.parent {
+        exts.push(ext::PARENT_SESSION, parent)
+            .push(ext::DELEGATION_DEPTH, [session.depth]);
+    }
+    if !context.subject.is_empty() {
+        exts.push(ext::SUBJECT, context.subject.as_bytes());
+    }
+    for scope in &context.scopes {
+        exts.push(ext::SCOPE, scope.as_bytes());
+    }
+    exts
+}
+
+/// Revokes a session together with every session delegated from it.
+///
+/// Returns one event per revoked session, the requested session first.
+pub async fn handle_revoke_token(cmd: RevokeTokenCommand, state: &AppState) -> Result<Vec<DomainEvent>> {
+    let session_id: [u8; 16] = hex::decode(&cmd.session_id)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Session IDs are 16 bytes"))?;
+    let events = state.sessions.revoke(&session_id)
+        .into_iter()
+        .map(|id| DomainEvent::TokenRevoked { session_id: hex::encode(id) })
+        .collect();
+    Ok(events)
+}
+
+/// Rebinds a session to a context changed outside of any request of its holder.
+///
+/// The generation is kept: the holder's token stops validating against the new context,
+/// but can be presented to refresh the session.
+pub async fn handle_change_context(cmd: ChangeContextCommand, state: &AppState) -> Result<DomainEvent> {
+    let session = state.sessions.get(&cmd.session_id)
+        .filter(|s| !s.revoked)
+        .ok_or_else(|| anyhow::anyhow!("Unknown or revoked session"))?;
+    let context = AuthContext {
+        risk_level: cmd.risk_level.unwrap_or(session.context.risk_level),
+        device_posture: cmd.device_posture.unwrap_or(session.context.device_posture),
+        ..session.context
+    };
+    state.sessions.rebind(&cmd.session_id, context)
+        .ok_or_else(|| anyhow::anyhow!("Unknown or revoked session"))?;
+    Ok(DomainEvent::ContextChanged { session_id: hex::encode(cmd.session_id) })
+}
+
+/// Requests step-up authentication for a live session.
+pub async fn handle_require_step_up(cmd: RequireStepUpCommand, state: &AppState) -> Result<DomainEvent> {
+    state.sessions.get(&cmd.session_id)
+        .filter(|s| !s.revoked)
+        .ok_or_else(|| anyhow::anyhow!("Unknown or revoked session"))?;
+    Ok(DomainEvent::StepUpRequired {
+        session_id: hex::encode(cmd.session_id),
+        reason: cmd.reason,
+    })
+}
+
+/// Makes `cmd.policy_version` the active policy set and rebinds every live session to
+/// it, so tokens issued under an older version stop validating until their holders
+/// refresh them.
+///
+/// Versions only move forward, so a delayed notification cannot reinstate an older
+/// policy set.
+pub async fn handle_update_policy(cmd: UpdatePolicyCommand, state: &AppState) -> Result<DomainEvent> {
+    let previous = state.policy_version.fetch_max(cmd.policy_version, Ordering::AcqRel);
+    if cmd.policy_version <= previous {
+        return Err(anyhow::anyhow!(
+            "Policy version {} is not newer than the active version {}",
+            cmd.policy_version,
+            previous
+        ));
+    }
+    state.sessions.rebind_all(|context| {
+        (context.policy_version < cmd.policy_version).then(|| AuthContext {
+            policy_version: cmd.policy_version,
+            ..context.clone()
+        })
+    });
+    Ok(DomainEvent::PolicyUpdated { policy_version: cmd.policy_version })
 }
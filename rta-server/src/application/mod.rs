@@ -1,3 +1,4 @@
 // src/application/mod.rs
 pub mod commands;
 pub mod queries;
+pub mod state;
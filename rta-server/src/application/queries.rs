@@ -1,7 +1,352 @@
 // src/application/queries.rs
-use anyhow::Result;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::hmac;
+use ring::rand::SystemRandom;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use thiserror::Error;
+use crate::application::state::AppState;
+use crate::config::ClientCredentials;
+use crate::domain::binding::{verify_binding, BindingEvidence};
+use rta_verify::caveat::AccessRequest;
+use crate::domain::session::Session;
+use crate::domain::token::{RTAToken, TokenError};
+use crate::infrastructure::pdp_adapter::{PolicyDecision, Subject, SubjectProperties};
 
-pub async fn query_token_details(session_id: &str) -> Result<String> {
-    // Return dummy data for demonstration.
-    Ok(format!("Token details for session_id: {}", session_id))
+/// The calling service could not be authenticated.
+#[derive(Debug, Error)]
+#[error("client authentication failed")]
+pub struct ClientAuthError;
+
+/// RFC 7662 introspection response for an RTAToken.
+///
+/// Inactive tokens are reported with `active: false` and no other members, so the
+/// response never reveals why a token was rejected.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TokenIntrospection {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+    /// RTA session ID (hex).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<&'static str>,
+}
+
+/// A token verification key in JWK format (RFC 8037 `OKP` key).
+#[derive(Debug, Clone, Serialize)]
+pub struct Jwk {
+    pub kty: &'static str,
+    pub crv: &'static str,
+    /// Base64url-encoded public key.
+    pub x: String,
+    /// Key ID as stamped into RTATokens and JWT headers, in decimal.
+    pub kid: String,
+    #[serde(rename = "use")]
+    pub key_use: &'static str,
+    pub alg: &'static str,
+}
+
+/// JWK Set of the keys that currently verify tokens (RFC 7517 §5).
+#[derive(Debug, Clone, Serialize)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+/// Publishes the pending, active and grace-period verification keys.
+///
+/// A key is listed before it signs and stays listed until it retires; verifiers that
+/// still meet an unknown key ID should refetch the set.
+pub fn verification_key_set(state: &AppState) -> JwkSet {
+    let keys = state.keyring.verification_keys()
+        .into_iter()
+        .map(|key| Jwk {
+            kty: "OKP",
+            crv: "Ed25519",
+            x: URL_SAFE_NO_PAD.encode(key.public_key),
+            kid: key.id.to_string(),
+            key_use: "sig",
+            alg: "EdDSA",
+        })
+        .collect();
+    JwkSet { keys }
+}
+
+/// Checks the credentials of a service calling the introspection endpoints.
+pub fn authenticate_client(state: &AppState, client_id: &str, client_secret: &str) -> Result<(), ClientAuthError> {
+    check_client_secret(&state.introspection_config.clients, client_id, client_secret)
+}
+
+/// Checks `client_secret` against the secret configured for `client_id` in `clients`.
+///
+/// The secrets are MACed under a fresh random key and the tags compared in constant
+/// time, so the time taken does not reveal how much of a secret was guessed. Unknown
+/// clients are checked against a placeholder secret and take as long as known ones.
+fn check_client_secret(clients: &[ClientCredentials], client_id: &str, client_secret: &str) -> Result<(), ClientAuthError> {
+    let key = hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new()).map_err(|_| ClientAuthError)?;
+    let known = clients.iter().find(|c| c.client_id == client_id);
+    let expected = hmac::sign(&key, known.map_or("", |c| c.client_secret.as_str()).as_bytes());
+    let matches = hmac::verify(&key, client_secret.as_bytes(), expected.as_ref()).is_ok();
+    if matches && known.is_some() {
+        Ok(())
+    } else {
+        Err(ClientAuthError)
+    }
+}
+
+/// Introspects an encoded RTAToken for a resource server.
+///
+/// The token is active if it decodes, its signature verifies with a current key, its
+/// session is live and of the same generation, and it matches the session's current
+/// context. Caveats of attenuated tokens are evaluated against `request`, the access
+/// the resource server is about to grant.
+pub fn introspect_token(encoded: &[u8], state: &AppState, request: &AccessRequest<'_>) -> TokenIntrospection {
+    let Ok(token) = RTAToken::decode(encoded) else {
+        return TokenIntrospection::default();
+    };
+    let session = match validate_session_state(&token, state, request) {
+        Ok(session) => session,
+        Err(_) => return TokenIntrospection::default(),
+    };
+    let scopes: Vec<&str> = session.context.scopes.iter().map(String::as_str).collect();
+    TokenIntrospection {
+        active: true,
+        sub: Some(session.context.subject.clone()).filter(|s| !s.is_empty()),
+        agent_id: Some(session.context.agent_id.clone()),
+        scope: Some(scopes.join(" ")),
+        aud: session.audience.clone(),
+        iat: Some(token.timestamp()),
+        exp: Some(token.expires_at(&state.token_config)),
+        sid: Some(hex::encode(session.session_id)),
+        generation: Some(session.generation),
+        token_type: Some("rtatoken"),
+    }
+}
+
+/// Validates `token`, as presented by its holder, against the current state of its
+/// session (see [`validate_session_state`]).
+///
+/// Connection-bound tokens additionally require `binding` evidence from the connection
+/// they are presented on, and fail with [`TokenError::BindingMismatch`] without it.
+pub fn validate_session_token(
+    token: &RTAToken,
+    state: &AppState,
+    binding: Option<&BindingEvidence>,
+    request: &AccessRequest<'_>,
+) -> Result<Session, TokenError> {
+    let session = validate_session_state(token, state, request)?;
+    if let Some(bound_to) = token.connection_binding() {
+        let evidence = binding.ok_or(TokenError::BindingMismatch)?;
+        if !verify_binding(bound_to, session.binding_secret.as_ref(), evidence) {
+            return Err(TokenError::BindingMismatch);
+        }
+    }
+    Ok(session)
+}
+
+/// Validates `token` against the current state of its session, without the holder
+/// check of connection-bound tokens.
+///
+/// Besides the signature and freshness checks, the token must belong to a live session,
+/// be of the session's current generation and match the session's current context.
+/// Caveats of attenuated tokens are evaluated against `request`. Only introspection
+/// skips the holder check: the resource server received the token from its presenter
+/// and cannot produce the holder's binding evidence.
+fn validate_session_state(
+    token: &RTAToken,
+    state: &AppState,
+    request: &AccessRequest<'_>,
+) -> Result<Session, TokenError> {
+    let session = state.sessions.get(&token.session_id)
+        .ok_or(TokenError::Revoked)?;
+    if session.revoked {
+        return Err(TokenError::Revoked);
+    }
+    if token.generation() != session.generation {
+        return Err(TokenError::StaleGeneration);
+    }
+    token.validate(&state.keyring, &session.context, &state.token_config, request)?;
+    Ok(session)
+}
+
+/// Validates `token` as proof that its presenter, on a connection with `binding`,
+/// holds the token's session, before the session is re-bound to a new context.
+///
+/// The signature must verify and the session be live and of the token's generation;
+/// the token may have expired or no longer match the session's context. A
+/// connection-bound token may be presented on any connection whose `binding` evidence
+/// proves it, such as one the holder reconnected or resumed on.
+pub fn validate_token_holder(
+    token: &RTAToken,
+    binding: &BindingEvidence,
+    state: &AppState,
+) -> Result<Session, TokenError> {
+    token.verify_signature(&state.keyring)?;
+    let session = state.sessions.get(&token.session_id)
+        .filter(|s| !s.revoked)
+        .ok_or(TokenError::Revoked)?;
+    if token.generation() != session.generation {
+        return Err(TokenError::StaleGeneration);
+    }
+    if let Some(bound_to) = token.connection_binding() {
+        if !verify_binding(bound_to, session.binding_secret.as_ref(), binding) {
+            return Err(TokenError::BindingMismatch);
+        }
+    }
+    Ok(session)
+}
+
+/// Validates `token` as proof that its presenter, on a connection with `binding`,
+/// holds the token's session and may revoke it. The token need not be current: an
+/// expired or stale token still proves holding the session.
+///
+/// `Ok(None)` if the session is unknown or already revoked; there is nothing left to
+/// revoke.
+pub fn validate_revocation(
+    token: &RTAToken,
+    binding: &BindingEvidence,
+    state: &AppState,
+) -> Result<Option<Session>, TokenError> {
+    token.verify_signature(&state.keyring)?;
+    let Some(session) = state.sessions.get(&token.session_id).filter(|s| !s.revoked) else {
+        return Ok(None);
+    };
+    if let Some(bound_to) = token.connection_binding() {
+        if !verify_binding(bound_to, session.binding_secret.as_ref(), binding) {
+            return Err(TokenError::BindingMismatch);
+        }
+    }
+    Ok(Some(session))
+}
+
+/// Decides whether the holder of `token` may perform `action` on `resource` now.
+///
+/// The token must validate against its session's current state, as presented by its
+/// holder, with caveats of attenuated tokens evaluated against the access. The PDP then
+/// decides on the access for the session's current context and `attributes`. A token
+/// that does not validate is a denial; an error means the PDP could not decide.
+///
+/// `binding` is the evidence of the presenting connection; without it, as before the
+/// connection's handshake is confirmed, tokens bound to a connection are denied.
+pub async fn authorize_action(
+    token: &RTAToken,
+    binding: Option<&BindingEvidence>,
+    action: &str,
+    resource: &str,
+    attributes: &Map<String, Value>,
+    client_ip: Option<std::net::IpAddr>,
+    state: &AppState,
+) -> anyhow::Result<PolicyDecision> {
+    let request = AccessRequest { resource: Some(resource), action: Some(action), client_ip };
+    let session = match validate_session_token(token, state, binding, &request) {
+        Ok(session) => session,
+        Err(e) => return Ok(PolicyDecision { permit: false, reason: Some(e.to_string()), ..Default::default() }),
+    };
+    let context = &session.context;
+    let subject = Subject {
+        kind: "agent",
+        id: &context.agent_id,
+        properties: SubjectProperties {
+            user: Some(context.subject.as_str()).filter(|s| !s.is_empty()),
+            provider: &context.provider,
+            scopes: context.scopes.iter().map(String::as_str).collect(),
+            session_id: hex::encode(session.session_id),
+            delegation_depth: session.depth,
+            risk_level: context.risk_level,
+            device_posture: context.device_posture,
+            client_ip,
+        },
+    };
+    state.pdp.evaluate(&subject, action, resource, attributes, state.policy_version()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::commands::{handle_refresh_token, RefreshTokenCommand};
+    use crate::domain::binding::{binding_proof, BINDING_LEN};
+    use crate::domain::context::{AuthContext, DevicePosture, RiskLevel};
+
+    fn clients() -> Vec<ClientCredentials> {
+        vec![
+            ClientCredentials { client_id: "files".to_string(), client_secret: "s3cret".to_string() },
+            ClientCredentials { client_id: "mail".to_string(), client_secret: "other secret".to_string() },
+        ]
+    }
+
+    #[test]
+    fn known_clients_authenticate_with_their_secret() {
+        assert!(check_client_secret(&clients(), "files", "s3cret").is_ok());
+        assert!(check_client_secret(&clients(), "mail", "other secret").is_ok());
+    }
+
+    #[test]
+    fn wrong_secrets_are_rejected() {
+        assert!(check_client_secret(&clients(), "files", "s3cre").is_err());
+        assert!(check_client_secret(&clients(), "files", "s3cret ").is_err());
+        assert!(check_client_secret(&clients(), "files", "other secret").is_err());
+        assert!(check_client_secret(&clients(), "files", "").is_err());
+    }
+
+    #[test]
+    fn unknown_clients_are_rejected() {
+        assert!(check_client_secret(&clients(), "unknown", "s3cret").is_err());
+        // Not even with the placeholder secret unknown clients are checked against.
+        assert!(check_client_secret(&clients(), "unknown", "").is_err());
+        assert!(check_client_secret(&[], "files", "s3cret").is_err());
+    }
+
+    fn context() -> AuthContext {
+        AuthContext {
+            subject: "alice".to_string(),
+            agent_id: "copilot".to_string(),
+            provider: "azure".to_string(),
+            scopes: ["files.read".to_string()].into(),
+            risk_level: RiskLevel::default(),
+            client_network: None,
+            device_posture: DevicePosture::default(),
+            policy_version: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn holder_refreshes_bound_session_from_second_connection() {
+        let state = AppState::for_tests();
+        let (first, second) = ([1u8; BINDING_LEN], [2u8; BINDING_LEN]);
+        let session_id = [7u8; 16];
+        state.sessions.insert(Session::new(session_id, context(), None, Some(first)));
+        let (token, _) = handle_refresh_token(RefreshTokenCommand { session_id, context: context() }, &state)
+            .await
+            .unwrap();
+
+        let on_first = BindingEvidence { exported: first, proof: None };
+        assert!(validate_token_holder(&token, &on_first, &state).is_ok());
+        // A second connection must prove the first one's keying material.
+        let unproven = BindingEvidence { exported: second, proof: None };
+        assert!(matches!(validate_token_holder(&token, &unproven, &state), Err(TokenError::BindingMismatch)));
+        let wrong = BindingEvidence { exported: second, proof: Some(binding_proof(&[3u8; BINDING_LEN], &second)) };
+        assert!(matches!(validate_token_holder(&token, &wrong, &state), Err(TokenError::BindingMismatch)));
+
+        let on_second = BindingEvidence { exported: second, proof: Some(binding_proof(&first, &second)) };
+        let current = validate_token_holder(&token, &on_second, &state).unwrap();
+        let cmd = RefreshTokenCommand { session_id, context: current.context };
+        let (refreshed, _) = handle_refresh_token(cmd, &state).await.unwrap();
+        assert_eq!(refreshed.generation(), token.generation() + 1);
+        assert!(validate_token_holder(&refreshed, &on_second, &state).is_ok());
+        assert!(matches!(validate_token_holder(&token, &on_second, &state), Err(TokenError::StaleGeneration)));
+    }
 }
@@ -0,0 +1,94 @@
+// src/application/state.rs
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use crate::config::{IdpProviders, IntrospectionConfig, JwtConfig, TokenConfig};
+use crate::domain::events::DomainEvent;
+use crate::domain::keyring::Keyring;
+use crate::infrastructure::metrics::TransportMetrics;
+use crate::infrastructure::nonce_cache::NonceCache;
+use crate::infrastructure::pdp_adapter::PdpClient;
+use crate::infrastructure::session_store::SessionStore;
+
+/// State shared by every connection and command handler.
+pub struct AppState {
+    pub token_config: TokenConfig,
+    pub jwt_config: JwtConfig,
+    pub idp_providers: IdpProviders,
+    pub pdp: PdpClient,
+    pub introspection_config: IntrospectionConfig,
+    pub keyring: Arc<Keyring>,
+    pub sessions: SessionStore,
+    /// Outstanding exchange challenges.
+    pub nonces: NonceCache,
+    /// Version of the active policy set, starting at the configured `pdp.policy_version`.
+    pub policy_version: AtomicU32,
+    /// Domain events of every command, for subscribers such as the push channels of
+    /// connected agents.
+    pub events: broadcast::Sender<DomainEvent>,
+    /// Connection and resumption counters of the QUIC listener.
+    pub metrics: Arc<TransportMetrics>,
+}
+
+impl AppState {
+    pub fn policy_version(&self) -> u32 {
+        self.policy_version.load(Ordering::Acquire)
+    }
+
+    /// Publishes events returned by a command handler.
+    pub fn publish(&self, events: impl IntoIterator<Item = DomainEvent>) {
+        for event in events {
+            // Sending fails only when nobody is subscribed.
+            let _ = self.events.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// State with a fresh signing key and no reachable IdP, PDP or introspection
+    /// clients, for tests of the command and query handlers.
+    pub(crate) fn for_tests() -> Self {
+        use crate::config::{IdpConfig, PdpConfig};
+        use crate::domain::clock::unix_now;
+        use crate::domain::keyring::KeyMaterial;
+        use ring::rand::SystemRandom;
+        use ring::signature::Ed25519KeyPair;
+
+        let token_config = TokenConfig {
+            max_age_secs: 3600,
+            clock_skew_secs: 30,
+            max_delegation_depth: 2,
+            nonce_ttl_secs: 60,
+            max_nonces_per_connection: 16,
+        };
+        let idp = IdpConfig {
+            introspection_url: "http://127.0.0.1:9/introspect".into(),
+            client_id: String::new(),
+            client_secret: String::new(),
+        };
+        let pdp = PdpConfig { endpoint: "http://127.0.0.1:9/decide".into(), policy_version: 1, timeout_ms: 100 };
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).expect("key generation failed");
+        let now = unix_now();
+        let keyring = Keyring::new(vec![KeyMaterial { id: 1, pkcs8: pkcs8.as_ref().to_vec(), created_at: now }], 7200, now)
+            .expect("keyring with one key");
+        Self {
+            sessions: SessionStore::new(token_config.session_ttl_secs()),
+            nonces: NonceCache::new(token_config.nonce_ttl_secs, token_config.max_nonces_per_connection),
+            token_config,
+            jwt_config: JwtConfig { issuer: "https://rta.test".into(), ttl_secs: 300 },
+            idp_providers: IdpProviders {
+                default: "azure".into(),
+                azure: idp.clone(),
+                okta: idp.clone(),
+                auth0: idp,
+            },
+            pdp: PdpClient::new(&pdp).expect("PDP client"),
+            introspection_config: IntrospectionConfig::default(),
+            keyring: Arc::new(keyring),
+            policy_version: AtomicU32::new(1),
+            events: broadcast::channel(16).0,
+            metrics: Arc::new(TransportMetrics::default()),
+        }
+    }
+}
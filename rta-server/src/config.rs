@@ -1,6 +1,7 @@
 // src/config.rs
 use config::{Config, ConfigError, File};
 use serde::Deserialize;
+use std::collections::HashMap;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct ServerConfig {
@@ -8,16 +9,157 @@ pub struct ServerConfig {
     pub port: u16,
     pub cert_path: String,
     pub key_path: String,
+    /// How many bidirectional and, separately, unidirectional streams a client may
+    /// have open at once on a connection.
+    #[serde(default = "default_max_concurrent_streams")]
+    pub max_concurrent_streams: u32,
+    /// How long a connection may stay silent before it is closed; clients keep
+    /// persistent sessions alive with more frequent keep-alives.
+    #[serde(default = "default_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    /// Whether QUIC clients authenticate with a certificate.
+    #[serde(default)]
+    pub client_auth: ClientAuth,
+    /// PEM bundle of the CAs that issue client certificates; required unless
+    /// `client_auth` is `none`.
+    pub client_ca_path: Option<String>,
+    /// Agent IDs of client certificate identities (SAN URI, SAN DNS name or CN) that
+    /// are not agent IDs themselves.
+    #[serde(default)]
+    pub client_agents: HashMap<String, String>,
+    /// Whether clients resuming a session may send requests in 0-RTT early data. Only
+    /// replay-safe requests are answered before the handshake is confirmed.
+    #[serde(default)]
+    pub early_data: bool,
+    /// Resumption tickets held for returning clients; each can be redeemed once.
+    #[serde(default = "default_session_cache_size")]
+    pub session_cache_size: usize,
+}
+
+/// Client certificate authentication on the QUIC listener.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientAuth {
+    /// Certificates are not requested.
+    #[default]
+    None,
+    /// Clients may present a certificate; if they do, it must verify.
+    Optional,
+    /// Clients without a verified certificate are refused in the handshake.
+    Required,
+}
+
+fn default_max_concurrent_streams() -> u32 {
+    100
+}
+
+fn default_idle_timeout_secs() -> u64 {
+    300
+}
+
+fn default_session_cache_size() -> usize {
+    1024
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct TokenConfig {
     pub max_age_secs: u64,
+    /// Tolerated clock difference between issuer and validator when checking freshness.
+    #[serde(default = "default_clock_skew_secs")]
+    pub clock_skew_secs: u64,
+    /// How many levels of delegated tokens may hang below a token issued by exchange.
+    #[serde(default = "default_max_delegation_depth")]
+    pub max_delegation_depth: u8,
+    /// How long an exchange challenge nonce can be redeemed.
+    #[serde(default = "default_nonce_ttl_secs")]
+    pub nonce_ttl_secs: u64,
+    /// How many unredeemed challenge nonces a connection may hold at once.
+    #[serde(default = "default_max_nonces_per_connection")]
+    pub max_nonces_per_connection: usize,
+}
+
+impl TokenConfig {
+    /// How long a session is kept after its latest token was issued: the token's
+    /// lifetime and the tolerated clock skew, plus one more lifetime during which the
+    /// expired token can still be refreshed.
+    pub fn session_ttl_secs(&self) -> u64 {
+        self.max_age_secs
+            .saturating_mul(2)
+            .saturating_add(self.clock_skew_secs)
+    }
+}
+
+fn default_nonce_ttl_secs() -> u64 {
+    60
+}
+
+fn default_max_nonces_per_connection() -> usize {
+    16
+}
+
+fn default_clock_skew_secs() -> u64 {
+    30
+}
+
+fn default_max_delegation_depth() -> u8 {
+    3
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct KeyringConfig {
+    /// Directory holding the PKCS#8 Ed25519 signing keys (`rta-signing-<id>.pem`) and
+    /// their creation times (`rta-signing-<id>.created`).
+    pub key_dir: String,
+    /// How long a key signs new tokens before a replacement is generated.
+    pub rotation_interval_secs: u64,
+    /// How long a superseded key keeps verifying tokens after rotation.
+    pub grace_period_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct JwtConfig {
+    /// `iss` claim of JWTs minted from RTATokens.
+    pub issuer: String,
+    /// Lifetime of minted JWTs; capped by the lifetime of the RTAToken they mirror.
+    pub ttl_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct HttpConfig {
+    /// Port of the plain HTTP endpoints, bound on `server.host`.
+    pub port: u16,
+    /// How long verifiers may cache the published key set.
+    #[serde(default = "default_jwks_max_age_secs")]
+    pub jwks_max_age_secs: u64,
+}
+
+fn default_jwks_max_age_secs() -> u64 {
+    300
+}
+
+/// Credentials of a service allowed to call the introspection endpoints.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct IntrospectionConfig {
+    #[serde(default)]
+    pub clients: Vec<ClientCredentials>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct RedisConfig {
     pub url: String,
+    /// Pub/sub channel on which identity providers and the PDP publish events.
+    #[serde(default = "default_events_channel")]
+    pub events_channel: String,
+}
+
+fn default_events_channel() -> String {
+    "rta:events".to_string()
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -38,12 +180,33 @@ pub struct IdpProviders {
 #[derive(Debug, Deserialize, Clone)]
 pub struct PdpConfig {
     pub endpoint: String,
+    /// Version of the active policy set; part of every token's authorization context,
+    /// so tokens issued under an older version stop validating once a newer one is
+    /// activated.
+    #[serde(default = "default_policy_version")]
+    pub policy_version: u32,
+    /// How long to wait for a decision before denying the access.
+    #[serde(default = "default_pdp_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_policy_version() -> u32 {
+    1
+}
+
+fn default_pdp_timeout_ms() -> u64 {
+    500
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Settings {
     pub server: ServerConfig,
     pub token: TokenConfig,
+    pub keyring: KeyringConfig,
+    pub jwt: JwtConfig,
+    pub http: HttpConfig,
+    #[serde(default)]
+    pub introspection: IntrospectionConfig,
     pub redis: RedisConfig,
     pub idp: IdpProviders,
     pub pdp: PdpConfig,
@@ -0,0 +1,106 @@
+// src/domain/binding.rs
+use ring::{digest, hmac};
+use std::convert::TryInto;
+
+/// TLS exporter label (RFC 5705 / RFC 8446 §7.5) used to derive connection bindings.
+pub const EXPORTER_LABEL: &[u8] = b"EXPORTER-RTA-token-binding";
+/// Length of the exported keying material and of the binding carried in tokens.
+pub const BINDING_LEN: usize = 32;
+
+/// Evidence that a token is being presented by the holder it was bound to.
+///
+/// `exported` is the keying material exported from the connection the token arrived
+/// on. A token presented on the connection it was issued on matches it directly; on
+/// any other connection the client must also supply `proof`, computed with
+/// [`binding_proof`] from the keying material of the original connection.
+#[derive(Debug, Clone, Copy)]
+pub struct BindingEvidence {
+    pub exported: [u8; BINDING_LEN],
+    pub proof: Option<[u8; BINDING_LEN]>,
+}
+
+/// The value signed into a token for a connection's exported keying material.
+///
+/// Tokens carry only this hash, so a leaked token does not disclose the keying
+/// material needed to prove the binding from another connection.
+pub fn binding_hash(exported: &[u8; BINDING_LEN]) -> [u8; BINDING_LEN] {
+    digest::digest(&digest::SHA256, exported)
+        .as_ref()
+        .try_into()
+        .expect("SHA-256 digests are 32 bytes")
+}
+
+/// Proof of possession of the original connection's keying material, bound to the
+/// connection it is presented on: `HMAC-SHA256(original, current)`.
+pub fn binding_proof(original: &[u8; BINDING_LEN], current: &[u8; BINDING_LEN]) -> [u8; BINDING_LEN] {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, original);
+    hmac::sign(&key, current)
+        .as_ref()
+        .try_into()
+        .expect("HMAC-SHA256 tags are 32 bytes")
+}
+
+/// Checks `evidence` against a token's `binding`.
+///
+/// `secret` is the original keying material recorded for the session at issuance; it
+/// is only needed when the token is presented on a different connection.
+pub fn verify_binding(
+    binding: &[u8; BINDING_LEN],
+    secret: Option<&[u8; BINDING_LEN]>,
+    evidence: &BindingEvidence,
+) -> bool {
+    if &binding_hash(&evidence.exported) == binding {
+        return true;
+    }
+    match (secret, evidence.proof) {
+        (Some(secret), Some(proof)) if &binding_hash(secret) == binding => {
+            let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+            hmac::verify(&key, &evidence.exported, &proof).is_ok()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: [u8; BINDING_LEN] = [1; BINDING_LEN];
+    const OTHER: [u8; BINDING_LEN] = [2; BINDING_LEN];
+
+    #[test]
+    fn same_connection_matches_without_proof() {
+        let evidence = BindingEvidence { exported: ORIGINAL, proof: None };
+        assert!(verify_binding(&binding_hash(&ORIGINAL), None, &evidence));
+        assert!(verify_binding(&binding_hash(&ORIGINAL), Some(&ORIGINAL), &evidence));
+    }
+
+    #[test]
+    fn other_connection_matches_with_valid_proof() {
+        let evidence = BindingEvidence { exported: OTHER, proof: Some(binding_proof(&ORIGINAL, &OTHER)) };
+        assert!(verify_binding(&binding_hash(&ORIGINAL), Some(&ORIGINAL), &evidence));
+    }
+
+    #[test]
+    fn other_connection_without_proof_is_rejected() {
+        let evidence = BindingEvidence { exported: OTHER, proof: None };
+        assert!(!verify_binding(&binding_hash(&ORIGINAL), Some(&ORIGINAL), &evidence));
+    }
+
+    #[test]
+    fn wrong_proof_is_rejected() {
+        // Keyed with the wrong material, or computed for a different connection.
+        let forged = BindingEvidence { exported: OTHER, proof: Some(binding_proof(&[3; BINDING_LEN], &OTHER)) };
+        assert!(!verify_binding(&binding_hash(&ORIGINAL), Some(&ORIGINAL), &forged));
+        let replayed = BindingEvidence { exported: OTHER, proof: Some(binding_proof(&ORIGINAL, &[4; BINDING_LEN])) };
+        assert!(!verify_binding(&binding_hash(&ORIGINAL), Some(&ORIGINAL), &replayed));
+    }
+
+    #[test]
+    fn proof_needs_the_recorded_secret() {
+        let evidence = BindingEvidence { exported: OTHER, proof: Some(binding_proof(&ORIGINAL, &OTHER)) };
+        assert!(!verify_binding(&binding_hash(&ORIGINAL), None, &evidence));
+        // A secret that is not the one the token was bound to does not help either.
+        assert!(!verify_binding(&binding_hash(&ORIGINAL), Some(&OTHER), &evidence));
+    }
+}
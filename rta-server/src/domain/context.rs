@@ -0,0 +1,155 @@
+// src/domain/context.rs
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+use std::net::IpAddr;
+use thiserror::Error;
+
+/// Version prefix of the canonical encoding; bump when the field set changes.
+const CANONICAL_VERSION: u8 = 1;
+
+/// Risk level assigned to a session by the IdP or a risk signal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskLevel {
+    #[default]
+    Unknown,
+    Low,
+    Medium,
+    High,
+}
+
+/// Security posture of the device the agent runs on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DevicePosture {
+    #[default]
+    Unknown,
+    Compliant,
+    NonCompliant,
+}
+
+/// The authorization context an RTAToken is bound to.
+///
+/// The token carries only the SHA-256 of [`AuthContext::canonical_bytes`]; any change
+/// to a field yields a different hash, so tokens issued under the old context no
+/// longer validate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthContext {
+    pub subject: String,
+    pub agent_id: String,
+    pub provider: String,
+    /// Kept sorted so the canonical encoding does not depend on insertion order.
+    pub scopes: BTreeSet<String>,
+    pub risk_level: RiskLevel,
+    pub client_network: Option<IpAddr>,
+    pub device_posture: DevicePosture,
+    pub policy_version: u32,
+}
+
+impl AuthContext {
+    /// Deterministic binary encoding of the context.
+    ///
+    /// Fields are written in declaration order after a one-byte encoding version.
+    /// Strings are a `u32` big-endian byte length followed by UTF-8; scopes are a `u32`
+    /// count followed by each scope in sorted order; enums are one byte; the client
+    /// network is a tag byte (0 = none, 4 = IPv4, 6 = IPv6) followed by the address.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut out = vec![CANONICAL_VERSION];
+        put_str(&mut out, &self.subject);
+        put_str(&mut out, &self.agent_id);
+        put_str(&mut out, &self.provider);
+        out.extend_from_slice(&(self.scopes.len() as u32).to_be_bytes());
+        for scope in &self.scopes {
+            put_str(&mut out, scope);
+        }
+        out.push(match self.risk_level {
+            RiskLevel::Unknown => 0,
+            RiskLevel::Low => 1,
+            RiskLevel::Medium => 2,
+            RiskLevel::High => 3,
+        });
+        match self.client_network {
+            None => out.push(0),
+            Some(IpAddr::V4(addr)) => {
+                out.push(4);
+                out.extend_from_slice(&addr.octets());
+            }
+            Some(IpAddr::V6(addr)) => {
+                out.push(6);
+                out.extend_from_slice(&addr.octets());
+            }
+        }
+        out.push(match self.device_posture {
+            DevicePosture::Unknown => 0,
+            DevicePosture::Compliant => 1,
+            DevicePosture::NonCompliant => 2,
+        });
+        out.extend_from_slice(&self.policy_version.to_be_bytes());
+        out
+    }
+
+    /// SHA-256 of the canonical encoding, as carried in `RTAToken.context_hash`.
+    pub fn hash(&self) -> [u8; 32] {
+        digest::digest(&digest::SHA256, &self.canonical_bytes())
+            .as_ref()
+            .try_into()
+            .expect("SHA-256 digests are 32 bytes")
+    }
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u32).to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// A requested scope that the grant does not cover.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("scope {0:?} is not granted")]
+pub struct ScopeNotGranted(pub String);
+
+/// Narrows `granted` to the `requested` scopes, which must all be granted; `None`
+/// keeps every granted scope.
+pub fn narrow_scopes(
+    granted: BTreeSet<String>,
+    requested: Option<BTreeSet<String>>,
+) -> Result<BTreeSet<String>, ScopeNotGranted> {
+    let Some(requested) = requested else {
+        return Ok(granted);
+    };
+    if let Some(extra) = requested.difference(&granted).next() {
+        return Err(ScopeNotGranted(extra.clone()));
+    }
+    Ok(requested)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn narrow_scopes_keeps_grant_without_request() {
+        let granted = scopes(&["files.read", "files.write"]);
+        assert_eq!(narrow_scopes(granted.clone(), None), Ok(granted));
+    }
+
+    #[test]
+    fn narrow_scopes_takes_requested_subset() {
+        let granted = scopes(&["files.read", "files.write"]);
+        assert_eq!(narrow_scopes(granted, Some(scopes(&["files.read"]))), Ok(scopes(&["files.read"])));
+    }
+
+    #[test]
+    fn narrow_scopes_rejects_ungranted_scope() {
+        let granted = scopes(&["files.read"]);
+        assert_eq!(
+            narrow_scopes(granted, Some(scopes(&["files.read", "files.write"]))),
+            Err(ScopeNotGranted("files.write".into())),
+        );
+    }
+}
@@ -0,0 +1,129 @@
+// src/domain/delegation.rs
+use std::collections::BTreeSet;
+use thiserror::Error;
+use crate::domain::context::{narrow_scopes, ScopeNotGranted};
+use crate::domain::session::Session;
+use crate::domain::token::RTAToken;
+
+/// Reasons a delegated token is refused.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum DelegationError {
+    #[error("delegation depth exhausted")]
+    DepthExhausted,
+    #[error("attenuated tokens cannot delegate")]
+    Attenuated,
+}
+
+/// Checks that `parent_token` may be delegated from at all.
+///
+/// A child must not shed the caveats its parent was narrowed by, and caveats restrict
+/// individual requests rather than anything a child token could inherit.
+pub fn check_parent_token(parent_token: &RTAToken) -> Result<(), DelegationError> {
+    if parent_token.attenuation().is_some_and(|a| a.caveats().next().is_some()) {
+        return Err(DelegationError::Attenuated);
+    }
+    Ok(())
+}
+
+/// Scopes of a child delegated from `parent`.
+///
+/// A child may only narrow its parent's scopes; `None` inherits them unchanged.
+pub fn attenuate_scopes(
+    parent: &Session,
+    requested: Option<BTreeSet<String>>,
+) -> Result<BTreeSet<String>, ScopeNotGranted> {
+    narrow_scopes(parent.context.scopes.clone(), requested)
+}
+
+/// Depth limit of a child of `parent` that may itself delegate `further` levels.
+///
+/// The parent's own limit always wins, so a chain can never grow deeper than the
+/// limit of the token it was started from.
+pub fn child_max_depth(parent: &Session, further: u8) -> Result<u8, DelegationError> {
+    if !parent.can_delegate() {
+        return Err(DelegationError::DepthExhausted);
+    }
+    let child_depth = parent.depth + 1;
+    Ok(child_depth.saturating_add(further).min(parent.max_depth))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::context::{AuthContext, DevicePosture, RiskLevel};
+    use crate::domain::keyring::{KeyMaterial, Keyring};
+    use ring::rand::SystemRandom;
+    use ring::signature::Ed25519KeyPair;
+    use rta_verify::caveat::Caveat;
+    use rta_verify::codec::Extensions;
+
+    fn scopes(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn parent(depth: u8, max_depth: u8) -> Session {
+        let context = AuthContext {
+            subject: "alice".to_string(),
+            agent_id: "copilot".to_string(),
+            provider: "okta".to_string(),
+            scopes: scopes(&["files.read", "files.write"]),
+            risk_level: RiskLevel::Low,
+            client_network: None,
+            device_posture: DevicePosture::Compliant,
+            policy_version: 1,
+        };
+        let mut session = Session::new([1; 16], context, None, None);
+        session.depth = depth;
+        session.max_depth = max_depth;
+        session
+    }
+
+    fn token(session: &Session, attenuable: bool) -> RTAToken {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let keyring = Keyring::new(vec![KeyMaterial { id: 1, pkcs8: pkcs8.as_ref().to_vec(), created_at: 0 }], 0, 0)
+            .unwrap();
+        RTAToken::issue(&keyring, session.session_id, &session.context, Extensions::new(), attenuable).unwrap()
+    }
+
+    #[test]
+    fn child_depth_is_capped_by_parent_limit() {
+        assert_eq!(child_max_depth(&parent(0, 3), 1), Ok(2));
+        assert_eq!(child_max_depth(&parent(0, 3), 10), Ok(3));
+        assert_eq!(child_max_depth(&parent(2, 3), 0), Ok(3));
+    }
+
+    #[test]
+    fn depth_limit_cannot_be_exceeded() {
+        assert_eq!(child_max_depth(&parent(3, 3), 0), Err(DelegationError::DepthExhausted));
+        assert_eq!(child_max_depth(&parent(0, 0), 5), Err(DelegationError::DepthExhausted));
+        let mut revoked = parent(0, 3);
+        revoked.revoked = true;
+        assert_eq!(child_max_depth(&revoked, 1), Err(DelegationError::DepthExhausted));
+    }
+
+    #[test]
+    fn child_inherits_or_narrows_scopes() {
+        let parent = parent(0, 3);
+        assert_eq!(attenuate_scopes(&parent, None), Ok(parent.context.scopes.clone()));
+        assert_eq!(attenuate_scopes(&parent, Some(scopes(&["files.read"]))), Ok(scopes(&["files.read"])));
+    }
+
+    #[test]
+    fn child_cannot_widen_scopes() {
+        assert_eq!(
+            attenuate_scopes(&parent(0, 3), Some(scopes(&["files.read", "mail.send"]))),
+            Err(ScopeNotGranted("mail.send".into())),
+        );
+    }
+
+    #[test]
+    fn attenuated_tokens_cannot_delegate() {
+        let session = parent(0, 3);
+        assert_eq!(check_parent_token(&token(&session, false)), Ok(()));
+        // Attenuable, but not narrowed yet.
+        let mut attenuable = token(&session, true);
+        assert_eq!(check_parent_token(&attenuable), Ok(()));
+        attenuable.attenuate(&Caveat::ResourcePrefix("/files/".into())).unwrap();
+        assert_eq!(check_parent_token(&attenuable), Err(DelegationError::Attenuated));
+    }
+}
@@ -1,8 +1,25 @@
 // src/domain/events.rs
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DomainEvent {
     TokenIssued { session_id: String },
     TokenRevoked { session_id: String },
+    /// A delegated token was issued on behalf of the holder of `parent_session_id`.
+    TokenDelegated { session_id: String, parent_session_id: String, depth: u8 },
+    /// A session's token was re-issued for a new context; earlier generations are invalid.
+    TokenRefreshed { session_id: String, generation: u32 },
+    /// A session's context changed without a new token; its current token no longer
+    /// validates until the holder refreshes it.
+    ContextChanged { session_id: String },
+    /// The holder of a session must authenticate again with the identity provider.
+    StepUpRequired { session_id: String, reason: Option<String> },
+    /// A new policy set became active; tokens refreshed from now on are bound to it.
+    PolicyUpdated { policy_version: u32 },
+    /// A signing key was published ahead of becoming active at `activates_at`.
+    SigningKeyPublished { key_id: u16, activates_at: u64 },
+    /// A new signing key became active; the previous key still verifies until `previous_retires_at`.
+    SigningKeyRotated { key_id: u16, previous_key_id: u16, previous_retires_at: u64 },
+    /// A grace-period signing key was removed and no longer verifies tokens.
+    SigningKeyRetired { key_id: u16 },
 }
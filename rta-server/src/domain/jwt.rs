@@ -0,0 +1,71 @@
+// src/domain/jwt.rs
+use serde::{Deserialize, Serialize};
+use rta_verify::caveat::{kind, Caveat};
+use crate::domain::token::RTAToken;
+
+/// Claims of a JWT mirroring an RTAToken, for backends that only understand JWTs.
+///
+/// The JWT is a short-lived snapshot: it does not follow context changes or
+/// revocation of the session, so its lifetime should stay well below the RTAToken's.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RtaJwtClaims {
+    pub iss: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    pub iat: u64,
+    pub exp: u64,
+    /// RTA session ID (hex).
+    pub sid: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    /// Space-separated scopes, as in RFC 8693 and RFC 9068.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub scope: String,
+    /// SHA-256 of the authorization context the RTAToken is bound to (hex).
+    pub ctx_hash: String,
+    /// Generation of the mirrored RTAToken within its session.
+    pub generation: u32,
+}
+
+impl RtaJwtClaims {
+    /// Claims mirroring `token`, issued at `now` and valid for at most `ttl_secs`.
+    ///
+    /// The JWT never outlives the RTAToken: expiry is capped at `token_expires_at`
+    /// and at any expiry caveat the token was attenuated with.
+    pub fn mirror(
+        token: &RTAToken,
+        issuer: &str,
+        audience: Option<&str>,
+        now: u64,
+        ttl_secs: u64,
+        token_expires_at: u64,
+    ) -> Self {
+        let caveat_expiry = token.attenuation()
+            .into_iter()
+            .flat_map(|a| a.caveats())
+            .filter(|(k, _)| *k == kind::EXPIRES_AT)
+            .filter_map(|(k, v)| match Caveat::decode(k, v) {
+                Some(Caveat::ExpiresAt(at)) => Some(at),
+                _ => None,
+            })
+            .min();
+        let exp = now.saturating_add(ttl_secs)
+            .min(token_expires_at)
+            .min(caveat_expiry.unwrap_or(u64::MAX));
+
+        Self {
+            iss: issuer.to_owned(),
+            sub: token.subject().map(str::to_owned),
+            aud: audience.map(str::to_owned),
+            iat: now,
+            exp,
+            sid: hex::encode(token.session_id),
+            agent_id: token.agent_id().map(str::to_owned),
+            scope: token.scopes().collect::<Vec<_>>().join(" "),
+            ctx_hash: hex::encode(token.context_hash()),
+            generation: token.generation(),
+        }
+    }
+}
@@ -0,0 +1,422 @@
+// src/domain/keyring.rs
+use anyhow::{anyhow, Result};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::Serialize;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use ring::{hkdf, hmac};
+use std::convert::TryInto;
+use std::sync::RwLock;
+use crate::domain::events::DomainEvent;
+use crate::domain::token::TokenError;
+
+pub use rta_verify::KeyId;
+
+/// Lifecycle state of a key held by the keyring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStatus {
+    /// Published ahead of becoming active at `activates_at` (unix seconds), so that
+    /// verifiers caching the key set know the key before it signs anything.
+    Pending { activates_at: u64 },
+    /// Signs newly issued tokens. Exactly one key is active at a time.
+    Active,
+    /// Superseded by a newer key; still verifies tokens until `retire_at` (unix seconds).
+    Grace { retire_at: u64 },
+}
+
+/// Public half of a key that currently verifies tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationKey {
+    pub id: KeyId,
+    /// Raw 32-byte Ed25519 public key.
+    pub public_key: [u8; 32],
+    pub status: KeyStatus,
+}
+
+/// Raw PKCS#8 key material as loaded from (or persisted to) the key store.
+pub struct KeyMaterial {
+    pub id: KeyId,
+    pub pkcs8: Vec<u8>,
+    /// Unix timestamp at which the key became, or is to become, the active signing key.
+    pub created_at: u64,
+}
+
+/// HKDF salt separating caveat root keys from any other use of the key material.
+const CAVEAT_ROOT_SALT: &[u8] = b"RTA caveat root v1";
+
+struct SigningKey {
+    id: KeyId,
+    key_pair: Ed25519KeyPair,
+    /// Root of the caveat chains of attenuable tokens signed by this key.
+    caveat_root: hmac::Key,
+    /// The same Ed25519 key, for signing JWTs.
+    jwt_key: EncodingKey,
+    created_at: u64,
+    status: KeyStatus,
+}
+
+/// Set of Ed25519 keys used to sign and verify RTATokens.
+///
+/// The most recent key is active and signs new tokens; superseded keys are kept in a
+/// grace period so that tokens issued shortly before a rotation remain verifiable.
+/// The next key may be published ahead of its activation.
+pub struct Keyring {
+    keys: RwLock<Vec<SigningKey>>,
+    grace_period_secs: u64,
+}
+
+impl Keyring {
+    /// Builds a keyring from persisted key material.
+    ///
+    /// The key with the highest ID that was created by `now` becomes active; keys
+    /// created after `now` are pending until then. Every earlier key is considered
+    /// superseded at the moment its successor was created and is dropped if its grace
+    /// period has already elapsed at `now`.
+    pub fn new(mut material: Vec<KeyMaterial>, grace_period_secs: u64, now: u64) -> Result<Self> {
+        if material.is_empty() {
+            return Err(anyhow!("Keyring requires at least one signing key"));
+        }
+        material.sort_by_key(|m| m.id);
+        let active = material.iter().rposition(|m| m.created_at <= now).unwrap_or(0);
+
+        let mut keys = Vec::with_capacity(material.len());
+        for (i, m) in material.iter().enumerate() {
+            let status = match i.cmp(&active) {
+                std::cmp::Ordering::Greater => KeyStatus::Pending { activates_at: m.created_at },
+                std::cmp::Ordering::Equal => KeyStatus::Active,
+                std::cmp::Ordering::Less => {
+                    let retire_at = material[i + 1].created_at.saturating_add(grace_period_secs);
+                    if retire_at <= now {
+                        continue;
+                    }
+                    KeyStatus::Grace { retire_at }
+                }
+            };
+            keys.push(signing_key(m, status)?);
+        }
+
+        Ok(Self { keys: RwLock::new(keys), grace_period_secs })
+    }
+
+    /// Signs with the active key. `signed_data` receives the active key ID so that the
+    /// ID can be covered by the signature; the ID is returned alongside the signature.
+    pub fn sign<F, E>(&self, signed_data: F) -> Result<(KeyId, Vec<u8>), E>
+    where
+        F: FnOnce(KeyId) -> Result<Vec<u8>, E>,
+    {
+        let keys = self.keys.read().expect("keyring lock poisoned");
+        let active = active_key(&keys);
+        let sig = active.key_pair.sign(&signed_data(active.id)?);
+        Ok((active.id, sig.as_ref().to_vec()))
+    }
+
+    /// Signs `claims` as an EdDSA JWT with the active key, whose ID is the `kid`.
+    pub fn sign_jwt<T: Serialize>(&self, claims: &T) -> Result<String> {
+        let keys = self.keys.read().expect("keyring lock poisoned");
+        let active = active_key(&keys);
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(active.id.to_string());
+        Ok(jsonwebtoken::encode(&header, claims, &active.jwt_key)?)
+    }
+
+    /// Verifies `sig` over `data` using the key named by `key_id`.
+    pub fn verify(&self, key_id: KeyId, data: &[u8], sig: &[u8]) -> Result<(), TokenError> {
+        let keys = self.keys.read().expect("keyring lock poisoned");
+        let key = keys.iter()
+            .find(|k| k.id == key_id)
+            .ok_or(TokenError::UnknownKey(key_id))?;
+        let public_key = key.key_pair.public_key().as_ref()
+            .try_into()
+            .expect("Ed25519 public keys are 32 bytes");
+        rta_verify::verify::verify_signature(public_key, data, sig).map_err(TokenError::from)
+    }
+
+    /// Caveat root key belonging to the key `key_id`.
+    ///
+    /// Derived from the signing key material, so it rotates and retires with it.
+    pub fn caveat_root(&self, key_id: KeyId) -> Result<hmac::Key, TokenError> {
+        let keys = self.keys.read().expect("keyring lock poisoned");
+        keys.iter()
+            .find(|k| k.id == key_id)
+            .map(|k| k.caveat_root.clone())
+            .ok_or(TokenError::UnknownKey(key_id))
+    }
+
+    /// Public keys of the pending, active and grace-period keys, in key ID order.
+    pub fn verification_keys(&self) -> Vec<VerificationKey> {
+        let keys = self.keys.read().expect("keyring lock poisoned");
+        keys.iter()
+            .map(|k| VerificationKey {
+                id: k.id,
+                public_key: k.key_pair.public_key().as_ref()
+                    .try_into()
+                    .expect("Ed25519 public keys are 32 bytes"),
+                status: k.status,
+            })
+            .collect()
+    }
+
+    /// ID of the key currently used to sign new tokens.
+    pub fn active_key_id(&self) -> KeyId {
+        let keys = self.keys.read().expect("keyring lock poisoned");
+        active_key(&keys).id
+    }
+
+    /// ID to assign to the next key added to the keyring.
+    pub fn next_key_id(&self) -> Result<KeyId> {
+        let keys = self.keys.read().expect("keyring lock poisoned");
+        keys.iter()
+            .map(|k| k.id)
+            .max()
+            .expect("keyring has an active key")
+            .checked_add(1)
+            .ok_or_else(|| anyhow!("Signing key IDs exhausted"))
+    }
+
+    /// When the active key's successor is due to take over, `interval_secs` after the
+    /// active key did; `None` while a successor is already pending.
+    pub fn next_activation(&self, interval_secs: u64) -> Option<u64> {
+        let keys = self.keys.read().expect("keyring lock poisoned");
+        if keys.iter().any(|k| matches!(k.status, KeyStatus::Pending { .. })) {
+            return None;
+        }
+        Some(active_key(&keys).created_at.saturating_add(interval_secs))
+    }
+
+    /// Adds `material` as the pending successor of the active key, to be activated by
+    /// [`Keyring::activate_due`] from `material.created_at` on.
+    pub fn publish(&self, material: KeyMaterial) -> Result<DomainEvent> {
+        let key = signing_key(&material, KeyStatus::Pending { activates_at: material.created_at })?;
+        let mut keys = self.keys.write().expect("keyring lock poisoned");
+        if keys.iter().any(|k| matches!(k.status, KeyStatus::Pending { .. })) {
+            return Err(anyhow!("A successor of the active signing key is already pending"));
+        }
+        check_successor(&keys, material.id)?;
+        keys.push(key);
+        Ok(DomainEvent::SigningKeyPublished { key_id: material.id, activates_at: material.created_at })
+    }
+
+    /// Activates the pending key once its activation time has come, moving the
+    /// previous key into its grace period.
+    pub fn activate_due(&self, now: u64) -> Option<DomainEvent> {
+        let mut keys = self.keys.write().expect("keyring lock poisoned");
+        let pending = keys.iter().position(|k| {
+            matches!(k.status, KeyStatus::Pending { activates_at } if activates_at <= now)
+        })?;
+        Some(self.activate(&mut keys, pending, now))
+    }
+
+    /// Installs `material` as the new active key right away and moves the previous one
+    /// into its grace period.
+    pub fn rotate(&self, material: KeyMaterial, now: u64) -> Result<DomainEvent> {
+        let key = signing_key(&material, KeyStatus::Pending { activates_at: now })?;
+        let mut keys = self.keys.write().expect("keyring lock poisoned");
+        check_successor(&keys, material.id)?;
+        keys.push(key);
+        let installed = keys.len() - 1;
+        Ok(self.activate(&mut keys, installed, now))
+    }
+
+    fn activate(&self, keys: &mut [SigningKey], index: usize, now: u64) -> DomainEvent {
+        let retire_at = now.saturating_add(self.grace_period_secs);
+        let previous = keys.iter_mut()
+            .find(|k| k.status == KeyStatus::Active)
+            .expect("keyring has no active key");
+        previous.status = KeyStatus::Grace { retire_at };
+        let previous_key_id = previous.id;
+        keys[index].status = KeyStatus::Active;
+        DomainEvent::SigningKeyRotated {
+            key_id: keys[index].id,
+            previous_key_id,
+            previous_retires_at: retire_at,
+        }
+    }
+
+    /// Drops grace-period keys whose overlap window has elapsed.
+    pub fn retire_expired(&self, now: u64) -> Vec<DomainEvent> {
+        let mut keys = self.keys.write().expect("keyring lock poisoned");
+        let mut events = Vec::new();
+        keys.retain(|k| match k.status {
+            KeyStatus::Grace { retire_at } if retire_at <= now => {
+                events.push(DomainEvent::SigningKeyRetired { key_id: k.id });
+                false
+            }
+            _ => true,
+        });
+        events
+    }
+}
+
+/// Keys only ever get higher IDs than every key before them.
+fn check_successor(keys: &[SigningKey], id: KeyId) -> Result<()> {
+    match keys.iter().map(|k| k.id).max() {
+        Some(highest) if id <= highest => Err(anyhow!("New key ID {} must be greater than {}", id, highest)),
+        _ => Ok(()),
+    }
+}
+
+fn active_key(keys: &[SigningKey]) -> &SigningKey {
+    keys.iter()
+        .find(|k| k.status == KeyStatus::Active)
+        .expect("keyring has no active key")
+}
+
+fn signing_key(material: &KeyMaterial, status: KeyStatus) -> Result<SigningKey> {
+    Ok(SigningKey {
+        id: material.id,
+        key_pair: parse_key(material)?,
+        caveat_root: derive_caveat_root(material),
+        jwt_key: EncodingKey::from_ed_der(&material.pkcs8),
+        created_at: material.created_at,
+        status,
+    })
+}
+
+fn parse_key(material: &KeyMaterial) -> Result<Ed25519KeyPair> {
+    // Accept both PKCS#8 v1 (as written by OpenSSL) and v2 (as generated by ring).
+    Ed25519KeyPair::from_pkcs8_maybe_unchecked(&material.pkcs8)
+        .map_err(|e| anyhow!("Invalid Ed25519 PKCS#8 key {}: {}", material.id, e))
+}
+
+fn derive_caveat_root(material: &KeyMaterial) -> hmac::Key {
+    let info = material.id.to_be_bytes();
+    let info = [info.as_slice()];
+    hkdf::Salt::new(hkdf::HKDF_SHA256, CAVEAT_ROOT_SALT)
+        .extract(&material.pkcs8)
+        .expand(&info, hmac::HMAC_SHA256)
+        .expect("HMAC-SHA256 key length is a valid HKDF output length")
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+
+    const GRACE: u64 = 100;
+
+    fn material(id: KeyId, created_at: u64) -> KeyMaterial {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        KeyMaterial { id, pkcs8: pkcs8.as_ref().to_vec(), created_at }
+    }
+
+    fn statuses(keyring: &Keyring) -> Vec<(KeyId, KeyStatus)> {
+        keyring.verification_keys().into_iter().map(|k| (k.id, k.status)).collect()
+    }
+
+    #[test]
+    fn new_activates_highest_id_regardless_of_order() {
+        let keyring = Keyring::new(vec![material(3, 1_000), material(1, 800), material(2, 950)], GRACE, 1_000)
+            .unwrap();
+        assert_eq!(keyring.active_key_id(), 3);
+        // Key 1 was superseded at 950 and retires at 1050; key 2 at 1000 and retires at 1100.
+        assert_eq!(statuses(&keyring), vec![
+            (1, KeyStatus::Grace { retire_at: 1_050 }),
+            (2, KeyStatus::Grace { retire_at: 1_100 }),
+            (3, KeyStatus::Active),
+        ]);
+    }
+
+    #[test]
+    fn new_drops_keys_past_their_grace_period() {
+        let keyring = Keyring::new(vec![material(2, 1_000), material(1, 500)], GRACE, 1_100).unwrap();
+        assert_eq!(statuses(&keyring), vec![(2, KeyStatus::Active)]);
+        assert!(matches!(keyring.verify(1, b"data", &[0; 64]), Err(TokenError::UnknownKey(1))));
+    }
+
+    #[test]
+    fn new_requires_a_key() {
+        assert!(Keyring::new(Vec::new(), GRACE, 0).is_err());
+    }
+
+    #[test]
+    fn rotation_moves_previous_key_into_grace() {
+        let keyring = Keyring::new(vec![material(1, 0)], GRACE, 0).unwrap();
+        assert_eq!(keyring.next_activation(60), Some(60));
+
+        let (_, old_sig) = keyring.sign(|_| Ok::<_, TokenError>(b"data".to_vec())).unwrap();
+        let event = keyring.rotate(material(keyring.next_key_id().unwrap(), 60), 60).unwrap();
+        assert_eq!(event, DomainEvent::SigningKeyRotated {
+            key_id: 2,
+            previous_key_id: 1,
+            previous_retires_at: 60 + GRACE,
+        });
+        assert_eq!(keyring.active_key_id(), 2);
+        assert_eq!(keyring.next_activation(60), Some(120));
+        assert_eq!(statuses(&keyring), vec![
+            (1, KeyStatus::Grace { retire_at: 60 + GRACE }),
+            (2, KeyStatus::Active),
+        ]);
+
+        // Tokens signed before the rotation still verify, and new ones use the new key.
+        keyring.verify(1, b"data", &old_sig).unwrap();
+        let (key_id, _) = keyring.sign(|id| Ok::<_, TokenError>(id.to_be_bytes().to_vec())).unwrap();
+        assert_eq!(key_id, 2);
+    }
+
+    #[test]
+    fn rotation_rejects_key_ids_that_do_not_increase() {
+        let keyring = Keyring::new(vec![material(5, 0)], GRACE, 0).unwrap();
+        assert!(keyring.rotate(material(5, 10), 10).is_err());
+        assert!(keyring.rotate(material(4, 10), 10).is_err());
+        assert_eq!(statuses(&keyring), vec![(5, KeyStatus::Active)]);
+    }
+
+    #[test]
+    fn published_keys_are_listed_before_they_sign() {
+        let keyring = Keyring::new(vec![material(1, 0)], GRACE, 0).unwrap();
+        let event = keyring.publish(material(keyring.next_key_id().unwrap(), 60)).unwrap();
+        assert_eq!(event, DomainEvent::SigningKeyPublished { key_id: 2, activates_at: 60 });
+        assert_eq!(statuses(&keyring), vec![
+            (1, KeyStatus::Active),
+            (2, KeyStatus::Pending { activates_at: 60 }),
+        ]);
+        assert_eq!(keyring.active_key_id(), 1);
+        assert_eq!(keyring.next_activation(60), None);
+        assert_eq!(keyring.next_key_id().unwrap(), 3);
+        assert!(keyring.publish(material(3, 120)).is_err());
+
+        assert_eq!(keyring.activate_due(59), None);
+        assert_eq!(keyring.activate_due(60), Some(DomainEvent::SigningKeyRotated {
+            key_id: 2,
+            previous_key_id: 1,
+            previous_retires_at: 60 + GRACE,
+        }));
+        assert_eq!(keyring.active_key_id(), 2);
+        assert_eq!(keyring.activate_due(u64::MAX), None);
+    }
+
+    #[test]
+    fn publish_rejects_key_ids_that_do_not_increase() {
+        let keyring = Keyring::new(vec![material(5, 0)], GRACE, 0).unwrap();
+        assert!(keyring.publish(material(5, 60)).is_err());
+        assert!(keyring.publish(material(4, 60)).is_err());
+        assert_eq!(statuses(&keyring), vec![(5, KeyStatus::Active)]);
+    }
+
+    #[test]
+    fn new_keeps_keys_created_in_the_future_pending() {
+        let keyring = Keyring::new(vec![material(1, 0), material(2, 500)], GRACE, 450).unwrap();
+        assert_eq!(statuses(&keyring), vec![
+            (1, KeyStatus::Active),
+            (2, KeyStatus::Pending { activates_at: 500 }),
+        ]);
+        assert!(keyring.activate_due(500).is_some());
+        assert_eq!(keyring.active_key_id(), 2);
+    }
+
+    #[test]
+    fn grace_keys_retire_once_their_overlap_elapses() {
+        let keyring = Keyring::new(vec![material(1, 0)], GRACE, 0).unwrap();
+        keyring.rotate(material(2, 10), 10).unwrap();
+        keyring.rotate(material(3, 50), 50).unwrap();
+
+        assert!(keyring.retire_expired(10 + GRACE - 1).is_empty());
+        assert_eq!(keyring.retire_expired(10 + GRACE), vec![DomainEvent::SigningKeyRetired { key_id: 1 }]);
+        assert_eq!(statuses(&keyring), vec![
+            (2, KeyStatus::Grace { retire_at: 50 + GRACE }),
+            (3, KeyStatus::Active),
+        ]);
+        assert_eq!(keyring.retire_expired(u64::MAX), vec![DomainEvent::SigningKeyRetired { key_id: 2 }]);
+        assert_eq!(keyring.active_key_id(), 3);
+    }
+}
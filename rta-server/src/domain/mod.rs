@@ -1,3 +1,10 @@
 // src/domain/mod.rs
 pub mod token;
 pub mod events;
+pub mod keyring;
+pub mod context;
+pub mod session;
+pub mod binding;
+pub mod delegation;
+pub mod jwt;
+pub mod clock;
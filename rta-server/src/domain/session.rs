@@ -0,0 +1,63 @@
+// src/domain/session.rs
+use crate::domain::binding::BINDING_LEN;
+use crate::domain::context::AuthContext;
+
+/// Server-side state of an RTA session.
+///
+/// A session outlives the individual tokens issued for it: every re-issuance keeps the
+/// session ID, rebinds the token to the current context and bumps the generation, so
+/// that only the most recent token of a session is accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub session_id: [u8; 16],
+    /// The context the current generation's token is bound to.
+    pub context: AuthContext,
+    /// Audience embedded in the session's tokens.
+    pub audience: Option<String>,
+    /// Generation of the most recently issued token; starts at 1.
+    pub generation: u32,
+    /// Unix time at which the current generation's token was issued.
+    pub issued_at: u64,
+    /// Keying material exported from the connection the session was established on.
+    /// Its hash is signed into every token of the session; the material itself never
+    /// leaves the server and is used to check binding proofs from other connections.
+    pub binding_secret: Option<[u8; BINDING_LEN]>,
+    /// Session this one was delegated from; `None` for sessions established by exchange.
+    pub parent: Option<[u8; 16]>,
+    /// Position in the delegation chain; 0 for sessions established by exchange.
+    pub depth: u8,
+    /// Deepest level the chain through this session may reach. The session can
+    /// delegate only while `depth < max_depth`.
+    pub max_depth: u8,
+    /// Whether the session's tokens carry a caveat chain holders can extend.
+    pub attenuable: bool,
+    pub revoked: bool,
+}
+
+impl Session {
+    pub fn new(
+        session_id: [u8; 16],
+        context: AuthContext,
+        audience: Option<String>,
+        binding_secret: Option<[u8; BINDING_LEN]>,
+    ) -> Self {
+        Self {
+            session_id,
+            context,
+            audience,
+            generation: 1,
+            issued_at: 0,
+            binding_secret,
+            parent: None,
+            depth: 0,
+            max_depth: 0,
+            attenuable: false,
+            revoked: false,
+        }
+    }
+
+    /// Whether tokens of this session may be used to obtain delegated child tokens.
+    pub fn can_delegate(&self) -> bool {
+        !self.revoked && self.depth < self.max_depth
+    }
+}
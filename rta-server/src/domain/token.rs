@@ -1,76 +1,325 @@
 // src/domain/token.rs
-use anyhow::Result;
-use ring::{digest, rand::{self, SecureRandom}, signature};
-use serde::{Serialize, Deserialize};
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use std::convert::TryInto;
-use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use crate::config::TokenConfig;
+use crate::domain::binding::BINDING_LEN;
+use crate::domain::clock::unix_now;
+use crate::domain::context::AuthContext;
+use crate::domain::keyring::{KeyId, Keyring};
+use rta_verify::caveat::{self, AccessRequest, Caveat};
+use rta_verify::codec::{
+    self, ext, Attenuation, Extensions, RTATokenRef, SignedFields, TokenFlags, TokenFormatError,
+    TokenHeader, TokenVersion, MAX_BASE64_TOKEN_LEN, SIGNATURE_LEN,
+};
+use rta_verify::verify::{self, Freshness, VerifyError};
 
-const TOKEN_HEADER: &[u8; 8] = b"RTA1TOKN";
+/// Reasons an RTAToken is rejected.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum TokenError {
+    #[error("invalid token signature")]
+    BadSignature,
+    #[error("token context does not match the current context")]
+    ContextMismatch,
+    #[error("token expired")]
+    Expired,
+    #[error("token issued in the future")]
+    NotYetValid,
+    #[error("unknown signing key {0}")]
+    UnknownKey(KeyId),
+    #[error("malformed token: {0}")]
+    MalformedHeader(#[from] TokenFormatError),
+    #[error("token is not valid Base64")]
+    BadEncoding,
+    #[error("token revoked")]
+    Revoked,
+    #[error("token superseded by a newer generation")]
+    StaleGeneration,
+    #[error("token is bound to a different connection")]
+    BindingMismatch,
+    #[error("invalid caveat chain")]
+    InvalidCaveatChain,
+    #[error("caveat of type {0:#04x} not satisfied")]
+    CaveatNotSatisfied(u8),
+    #[error("token is not valid for the requested audience")]
+    AudienceMismatch,
+}
+
+impl From<VerifyError> for TokenError {
+    fn from(e: VerifyError) -> Self {
+        match e {
+            VerifyError::Malformed(e) => TokenError::MalformedHeader(e),
+            VerifyError::UnknownKey(id) => TokenError::UnknownKey(id),
+            VerifyError::BadSignature => TokenError::BadSignature,
+            VerifyError::NotYetValid => TokenError::NotYetValid,
+            VerifyError::Expired => TokenError::Expired,
+            VerifyError::Attenuable | VerifyError::InvalidCaveatChain => TokenError::InvalidCaveatChain,
+            VerifyError::CaveatNotSatisfied(kind) => TokenError::CaveatNotSatisfied(kind),
+        }
+    }
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RTAToken {
-    header: [u8; 8],
+    header: TokenHeader,
     pub session_id: [u8; 16],
     context_hash: [u8; 32],
     timestamp: u64,
-    signature: Vec<u8>,
+    extensions: Extensions,
+    signature: [u8; SIGNATURE_LEN],
+    /// Present exactly when the header carries [`TokenFlags::ATTENUABLE`].
+    attenuation: Option<Attenuation>,
 }
 
 impl RTAToken {
-    pub fn issue(session_id: [u8; 16], context_data: &[u8]) -> Result<Self> {
-        let rng = rand::SystemRandom::new();
-        // In production, load a persistent signing key.
-        let pkcs8_bytes = signature::Ed25519KeyPair::generate_pkcs8(&rng)?;
-        let signing_key = signature::Ed25519KeyPair::from_pkcs8(pkcs8_bytes.as_ref())?;
-        let context_hash = digest::digest(&digest::SHA256, context_data);
-        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-
-        let mut token_data = Vec::new();
-        token_data.extend_from_slice(TOKEN_HEADER);
-        token_data.extend_from_slice(&session_id);
-        token_data.extend_from_slice(context_hash.as_ref());
-        token_data.extend_from_slice(&timestamp.to_be_bytes());
-        let sig = signing_key.sign(&token_data);
+    /// Issues a token for `session_id` bound to the hash of `context`.
+    ///
+    /// Non-empty `extensions` are embedded in the signed extension section. An
+    /// `attenuable` token starts an empty caveat chain that holders can extend with
+    /// [`RTAToken::attenuate`].
+    pub fn issue(
+        keyring: &Keyring,
+        session_id: [u8; 16],
+        context: &AuthContext,
+        extensions: Extensions,
+        attenuable: bool,
+    ) -> Result<Self> {
+        let context_hash = context.hash();
+        let timestamp = unix_now();
+
+        let mut flags = TokenFlags::empty();
+        if !extensions.is_empty() {
+            flags = flags.union(TokenFlags::HAS_EXTENSIONS);
+        }
+        if extensions.get(ext::CONNECTION_BINDING).is_some() {
+            flags = flags.union(TokenFlags::BOUND);
+        }
+        if extensions.get(ext::PARENT_SESSION).is_some() {
+            flags = flags.union(TokenFlags::DELEGATED);
+        }
+        if attenuable {
+            flags = flags.union(TokenFlags::ATTENUABLE);
+        }
+        let mut header = TokenHeader {
+            version: TokenVersion::CURRENT,
+            key_id: 0,
+            flags,
+        };
+        let (key_id, sig) = keyring.sign(|key_id| {
+            header.key_id = key_id;
+            codec::encode_signed(&SignedFields {
+                header,
+                session_id: &session_id,
+                context_hash: &context_hash,
+                timestamp,
+                extensions: &extensions,
+            })
+        })?;
+        header.key_id = key_id;
+        let attenuation = if attenuable {
+            let root = keyring.caveat_root(key_id)?;
+            Some(Attenuation::new(caveat::initial_tag(&root, &sig)))
+        } else {
+            None
+        };
 
         Ok(Self {
-            header: *TOKEN_HEADER,
+            header,
             session_id,
-            context_hash: context_hash.as_ref().try_into()?,
+            context_hash,
             timestamp,
-            signature: sig.as_ref().to_vec(),
+            extensions,
+            signature: sig.as_slice().try_into()?,
+            attenuation,
         })
     }
 
-    pub fn validate(&self, context_data: &[u8], max_age_secs: u64) -> Result<()> {
-        let mut token_data = Vec::new();
-        token_data.extend_from_slice(&self.header);
-        token_data.extend_from_slice(&self.session_id);
-        token_data.extend_from_slice(&self.context_hash);
-        token_data.extend_from_slice(&self.timestamp.to_be_bytes());
+    /// Narrows an attenuable token by appending `caveat` to its chain.
+    ///
+    /// Needs no keys: the new tag is derived from the current one, which the holder
+    /// already has. Fails if the token is not attenuable or the caveat section is full.
+    pub fn attenuate(&mut self, caveat: &Caveat) -> Result<()> {
+        let attenuation = self.attenuation.as_mut()
+            .ok_or_else(|| anyhow!("Token was not issued as attenuable"))?;
+        let (kind, value) = caveat.encode();
+        let tag = caveat::chain_tag(attenuation.tag(), kind, &value);
+        attenuation.push(kind, value, tag)?;
+        Ok(())
+    }
 
-        // In production, retrieve the persistent public key from configuration.
-        let public_key_bytes = include_bytes!("../../certs/public_key.der");
-        let verifying_key = signature::UnparsedPublicKey::new(&signature::ED25519, public_key_bytes);
-        verifying_key.verify(&token_data, &self.signature)
-            .map_err(|_| anyhow::anyhow!("Invalid signature"))?;
+    /// Checks the signature, the binding to the session's current `context`, the
+    /// freshness of the token and, for attenuable tokens, the caveat chain and every
+    /// caveat against `request`.
+    ///
+    /// Clocks of the issuing and validating hosts may disagree by up to
+    /// `config.clock_skew_secs` in either direction.
+    pub fn validate(
+        &self,
+        keyring: &Keyring,
+        context: &AuthContext,
+        config: &TokenConfig,
+        request: &AccessRequest<'_>,
+    ) -> Result<(), TokenError> {
+        self.verify_signature(keyring)?;
 
-        let expected_hash = digest::digest(&digest::SHA256, context_data);
-        if expected_hash.as_ref() != self.context_hash {
-            return Err(anyhow::anyhow!("Context mismatch"));
+        if context.hash() != self.context_hash {
+            return Err(TokenError::ContextMismatch);
         }
 
-        let current_ts = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-        if current_ts - self.timestamp > max_age_secs {
-            return Err(anyhow::anyhow!("Token expired"));
+        let current_ts = unix_now();
+        freshness(config).check(self.timestamp, current_ts)?;
+
+        if let Some(attenuation) = &self.attenuation {
+            let root = keyring.caveat_root(self.header.key_id)?;
+            verify::check_caveats(&root, &self.signature, attenuation, request, current_ts)?;
         }
         Ok(())
     }
 
-    pub fn serialize(&self) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(self)?)
+    /// Checks only the signature, for callers that re-bind the token's session to a
+    /// new context and so expect the old context and freshness to be out of date.
+    pub fn verify_signature(&self, keyring: &Keyring) -> Result<(), TokenError> {
+        keyring.verify(self.header.key_id, &self.signed_data(), &self.signature)
+    }
+
+    /// Unix time at which the token expires, before clock-skew tolerance.
+    pub fn expires_at(&self, config: &TokenConfig) -> u64 {
+        freshness(config).expires_at(self.timestamp)
+    }
+
+    pub fn version(&self) -> TokenVersion {
+        self.header.version
+    }
+
+    /// ID of the keyring key that signed this token.
+    pub fn key_id(&self) -> KeyId {
+        self.header.key_id
+    }
+
+    pub fn flags(&self) -> TokenFlags {
+        self.header.flags
+    }
+
+    pub fn context_hash(&self) -> &[u8; 32] {
+        &self.context_hash
+    }
+
+    /// Issuance time in unix seconds.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// All signed extension entries.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Agent the token was issued to, if embedded.
+    pub fn agent_id(&self) -> Option<&str> {
+        self.extensions.get_str(ext::AGENT_ID)
+    }
+
+    /// Subject the agent acts for, if embedded.
+    pub fn subject(&self) -> Option<&str> {
+        self.extensions.get_str(ext::SUBJECT)
     }
 
-    pub fn deserialize(token_bytes: &[u8]) -> Result<Self> {
-        Ok(bincode::deserialize(token_bytes)?)
+    /// IdP that authenticated the subject, if embedded.
+    pub fn provider(&self) -> Option<&str> {
+        self.extensions.get_str(ext::PROVIDER)
+    }
+
+    /// Intended audience, if embedded.
+    pub fn audience(&self) -> Option<&str> {
+        self.extensions.get_str(ext::AUDIENCE)
+    }
+
+    /// Scopes granted to the agent, in issuance order.
+    pub fn scopes(&self) -> impl Iterator<Item = &str> {
+        self.extensions.get_all(ext::SCOPE)
+            .filter_map(|v| std::str::from_utf8(v).ok())
+    }
+
+    /// Re-issuance generation within the session; tokens without one count as generation 0.
+    pub fn generation(&self) -> u32 {
+        self.extensions.get_u32(ext::GENERATION).unwrap_or(0)
+    }
+
+    /// Hash of the keying material of the connection the token is bound to, if bound.
+    pub fn connection_binding(&self) -> Option<&[u8; BINDING_LEN]> {
+        self.extensions.get(ext::CONNECTION_BINDING)
+            .and_then(|v| v.try_into().ok())
+    }
+
+    /// Session this token was delegated from, if it is a delegated token.
+    pub fn parent_session(&self) -> Option<&[u8; 16]> {
+        self.extensions.get(ext::PARENT_SESSION)
+            .and_then(|v| v.try_into().ok())
+    }
+
+    /// Position in the delegation chain; 0 for tokens issued by exchange.
+    pub fn delegation_depth(&self) -> u8 {
+        self.extensions.get(ext::DELEGATION_DEPTH)
+            .map_or(0, |v| v[0])
+    }
+
+    /// Caveats and chain tag of an attenuable token.
+    pub fn attenuation(&self) -> Option<&Attenuation> {
+        self.attenuation.as_ref()
+    }
+
+    /// Encodes the token in its canonical binary layout (see [`codec`]).
+    pub fn encode(&self) -> Vec<u8> {
+        codec::encode(&self.signed_data(), &self.signature, self.attenuation.as_ref())
+    }
+
+    /// Decodes a v1 or v2 token, rejecting anything that is not exactly one canonical encoding.
+    pub fn decode(token_bytes: &[u8]) -> Result<Self, TokenFormatError> {
+        Ok(RTATokenRef::parse(token_bytes)?.into())
+    }
+
+    /// Decodes a Base64-encoded token as presented in requests.
+    pub fn decode_base64(encoded: &str) -> Result<Self, TokenError> {
+        Ok(Self::decode(&decode_base64(encoded)?)?)
+    }
+
+    fn signed_data(&self) -> Vec<u8> {
+        codec::encode_signed(&SignedFields {
+            header: self.header,
+            session_id: &self.session_id,
+            context_hash: &self.context_hash,
+            timestamp: self.timestamp,
+            extensions: &self.extensions,
+        })
+        .expect("extensions were size-checked when the token was issued or decoded")
+    }
+}
+
+/// Decodes the Base64 encoding of a token without parsing it. Input longer than any
+/// valid token is rejected before it is decoded.
+pub fn decode_base64(encoded: &str) -> Result<Vec<u8>, TokenError> {
+    if encoded.len() > MAX_BASE64_TOKEN_LEN {
+        return Err(TokenFormatError::TooLong { max: MAX_BASE64_TOKEN_LEN, actual: encoded.len() }.into());
+    }
+    STANDARD.decode(encoded).map_err(|_| TokenError::BadEncoding)
+}
+
+fn freshness(config: &TokenConfig) -> Freshness {
+    Freshness { max_age_secs: config.max_age_secs, clock_skew_secs: config.clock_skew_secs }
+}
+
+impl From<RTATokenRef<'_>> for RTAToken {
+    fn from(token: RTATokenRef<'_>) -> Self {
+        Self {
+            header: token.header(),
+            session_id: *token.session_id(),
+            context_hash: *token.context_hash(),
+            timestamp: token.timestamp(),
+            extensions: token.extensions().into(),
+            signature: *token.signature(),
+            attenuation: token.attenuation(),
+        }
     }
 }
@@ -0,0 +1,206 @@
+// src/infrastructure/client_auth.rs
+//! Client certificate authentication of agents on the QUIC listener.
+//!
+//! A verified client certificate names the agent behind a connection. Its identities
+//! are tried in order of preference: SAN URIs, SAN DNS names, then the subject CN. The
+//! first identity listed in `server.client_agents` is mapped to the agent ID given
+//! there; if none is listed, the most preferred identity is the agent ID itself.
+use anyhow::{anyhow, Result};
+use quinn::Connection;
+use rustls::pki_types::CertificateDer;
+use rustls::server::danger::ClientCertVerifier;
+use rustls::server::WebPkiClientVerifier;
+use rustls::RootCertStore;
+use rustls_pemfile::certs;
+use std::collections::HashMap;
+use std::{fs, sync::Arc};
+use thiserror::Error;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+use crate::config::{ClientAuth, ServerConfig};
+
+/// The `agent_id` of a request is not the agent authenticated by the connection's
+/// client certificate.
+#[derive(Debug, Error)]
+#[error("agent_id {claimed:?} does not match the client certificate's agent {certified:?}")]
+pub struct AgentMismatch {
+    pub claimed: String,
+    pub certified: String,
+}
+
+/// Verifier of client certificates for `config.client_auth`, or `None` if clients are
+/// not asked for one.
+pub fn client_verifier(config: &ServerConfig) -> Result<Option<Arc<dyn ClientCertVerifier>>> {
+    if config.client_auth == ClientAuth::None {
+        return Ok(None);
+    }
+    let ca_path = config.client_ca_path.as_deref()
+        .ok_or_else(|| anyhow!("client_auth = {:?} requires client_ca_path", config.client_auth))?;
+    let mut roots = RootCertStore::empty();
+    let mut reader = std::io::BufReader::new(fs::File::open(ca_path)?);
+    for cert in certs(&mut reader) {
+        roots.add(cert?)?;
+    }
+    let builder = WebPkiClientVerifier::builder(Arc::new(roots));
+    let verifier = match config.client_auth {
+        ClientAuth::Optional => builder.allow_unauthenticated().build()?,
+        _ => builder.build()?,
+    };
+    Ok(Some(verifier))
+}
+
+/// Maps verified client certificates to agent IDs.
+#[derive(Debug, Clone, Default)]
+pub struct AgentMapping {
+    agents: HashMap<String, String>,
+}
+
+impl AgentMapping {
+    pub fn new(config: &ServerConfig) -> Self {
+        Self { agents: config.client_agents.clone() }
+    }
+
+    /// The agent authenticated by `conn`'s client certificate; `None` if the client
+    /// presented none. The handshake has already verified the certificate.
+    pub fn agent_for(&self, conn: &Connection) -> Result<Option<String>> {
+        let Some(identity) = conn.peer_identity() else {
+            return Ok(None);
+        };
+        let chain = identity.downcast::<Vec<CertificateDer<'static>>>()
+            .map_err(|_| anyhow!("Unexpected peer identity type"))?;
+        let Some(end_entity) = chain.first() else {
+            return Ok(None);
+        };
+        self.agent_for_certificate(end_entity).map(Some)
+    }
+
+    /// The agent named by a verified end-entity certificate.
+    fn agent_for_certificate(&self, der: &CertificateDer<'_>) -> Result<String> {
+        let identities = certificate_identities(der)?;
+        let agent = identities.iter()
+            .find_map(|id| self.agents.get(id))
+            .or(identities.first())
+            .ok_or_else(|| anyhow!("Client certificate names no SAN URI, DNS name or CN"))?;
+        Ok(agent.clone())
+    }
+}
+
+/// Identities named by a certificate, in order of preference.
+fn certificate_identities(der: &CertificateDer<'_>) -> Result<Vec<String>> {
+    let (_, cert) = X509Certificate::from_der(der.as_ref())
+        .map_err(|e| anyhow!("Failed to parse client certificate: {}", e))?;
+    let mut uris = Vec::new();
+    let mut dns_names = Vec::new();
+    if let Some(san) = cert.subject_alternative_name()
+        .map_err(|e| anyhow!("Invalid subjectAltName: {}", e))?
+    {
+        for name in &san.value.general_names {
+            match name {
+                GeneralName::URI(uri) => uris.push(uri.to_string()),
+                GeneralName::DNSName(dns) => dns_names.push(dns.to_string()),
+                _ => {}
+            }
+        }
+    }
+    let common_name = cert.subject().iter_common_name()
+        .next()
+        .and_then(|cn| cn.as_str().ok())
+        .map(str::to_owned);
+    Ok(uris.into_iter().chain(dns_names).chain(common_name).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Self-signed Ed25519 certificates; only their names matter here.
+    /// CN `copilot-cn`; SAN DNS `copilot.example.org`, URI
+    /// `spiffe://example.org/agents/copilot` and DNS `other.example.org`, in that order.
+    const SAN_URI_DNS_CN: &str = "\
+        -----BEGIN CERTIFICATE-----\n\
+        MIIBmzCCAU2gAwIBAgIUFdgz+TvxfSYFryps38bO63C87x8wBQYDK2VwMBUxEzAR\n\
+        BgNVBAMMCmNvcGlsb3QtY24wIBcNMjYxMDE3MDY0MjMyWhgPMjEyNjA5MjMwNjQy\n\
+        MzJaMBUxEzARBgNVBAMMCmNvcGlsb3QtY24wKjAFBgMrZXADIQBSD5Mnt//BaCLh\n\
+        4TPaCwNnIiI6sHbQIOw71tDGE+mvs6OBrDCBqTAdBgNVHQ4EFgQU210LFVW1EMTB\n\
+        FiqcXFmmTPJHqggwHwYDVR0jBBgwFoAU210LFVW1EMTBFiqcXFmmTPJHqggwDwYD\n\
+        VR0TAQH/BAUwAwEB/zBWBgNVHREETzBNghNjb3BpbG90LmV4YW1wbGUub3JnhiNz\n\
+        cGlmZmU6Ly9leGFtcGxlLm9yZy9hZ2VudHMvY29waWxvdIIRb3RoZXIuZXhhbXBs\n\
+        ZS5vcmcwBQYDK2VwA0EAWZdPzpG38SDxafrA94Nv78Y7Q0ls5112pxhi6ZJqzmVj\n\
+        bm03WPlslUx0+LqKDcayNEWp0BvEkqQ4QKwYNdyWDA==\n\
+        -----END CERTIFICATE-----\n";
+    /// CN `copilot-cn`; SAN DNS `copilot.example.org`.
+    const SAN_DNS_CN: &str = "\
+        -----BEGIN CERTIFICATE-----\n\
+        MIIBYTCCAROgAwIBAgIUePM8JV1+o+P4Zp0mLb4nj9oL1N4wBQYDK2VwMBUxEzAR\n\
+        BgNVBAMMCmNvcGlsb3QtY24wIBcNMjYxMDE3MDY0MjMyWhgPMjEyNjA5MjMwNjQy\n\
+        MzJaMBUxEzARBgNVBAMMCmNvcGlsb3QtY24wKjAFBgMrZXADIQBSD5Mnt//BaCLh\n\
+        4TPaCwNnIiI6sHbQIOw71tDGE+mvs6NzMHEwHQYDVR0OBBYEFNtdCxVVtRDEwRYq\n\
+        nFxZpkzyR6oIMB8GA1UdIwQYMBaAFNtdCxVVtRDEwRYqnFxZpkzyR6oIMA8GA1Ud\n\
+        EwEB/wQFMAMBAf8wHgYDVR0RBBcwFYITY29waWxvdC5leGFtcGxlLm9yZzAFBgMr\n\
+        ZXADQQA+eNX3307YK7uGKkOegstjv5Znsj+tTfvsI5yMOuiI1IYlSUwq/DDB/jhF\n\
+        lnaDADT5UVqOyrD/jB/kF15Ug4gO\n\
+        -----END CERTIFICATE-----\n";
+    /// CN `copilot-cn` only.
+    const CN_ONLY: &str = "\
+        -----BEGIN CERTIFICATE-----\n\
+        MIIBQDCB86ADAgECAhR3QbvB7DF4vQDMoLn3Fj4iBfB3IjAFBgMrZXAwFTETMBEG\n\
+        A1UEAwwKY29waWxvdC1jbjAgFw0yNjEwMTcwNjQyMzJaGA8yMTI2MDkyMzA2NDIz\n\
+        MlowFTETMBEGA1UEAwwKY29waWxvdC1jbjAqMAUGAytlcAMhAFIPkye3/8FoIuHh\n\
+        M9oLA2ciIjqwdtAg7DvW0MYT6a+zo1MwUTAdBgNVHQ4EFgQU210LFVW1EMTBFiqc\n\
+        XFmmTPJHqggwHwYDVR0jBBgwFoAU210LFVW1EMTBFiqcXFmmTPJHqggwDwYDVR0T\n\
+        AQH/BAUwAwEB/zAFBgMrZXADQQCy0fnUUS59VEASucdWlLLUE1Mv1v8dQa03dvk+\n\
+        BZZMbT/A+lBmieWZ4hhFXPSm2HlJyOed7SilvgKy5yDZ9f8K\n\
+        -----END CERTIFICATE-----\n";
+    /// Neither SAN nor CN, only O `Example`.
+    const NO_IDENTITY: &str = "\
+        -----BEGIN CERTIFICATE-----\n\
+        MIIBOjCB7aADAgECAhRwpOknF9BZZNWkzD0t5UH/TM6NNDAFBgMrZXAwEjEQMA4G\n\
+        A1UECgwHRXhhbXBsZTAgFw0yNjEwMTcwNjQyMzJaGA8yMTI2MDkyMzA2NDIzMlow\n\
+        EjEQMA4GA1UECgwHRXhhbXBsZTAqMAUGAytlcAMhAFIPkye3/8FoIuHhM9oLA2ci\n\
+        IjqwdtAg7DvW0MYT6a+zo1MwUTAdBgNVHQ4EFgQU210LFVW1EMTBFiqcXFmmTPJH\n\
+        qggwHwYDVR0jBBgwFoAU210LFVW1EMTBFiqcXFmmTPJHqggwDwYDVR0TAQH/BAUw\n\
+        AwEB/zAFBgMrZXADQQAjsRaLjezFy5NRahKd6rbPPXye+cXBQXHAAVT8Ae6nEH54\n\
+        y4RB7SR/eiyrJY77QHrA2zHnA6L8JgPTtDK0l9cN\n\
+        -----END CERTIFICATE-----\n";
+
+    fn der(pem: &str) -> CertificateDer<'static> {
+        certs(&mut pem.as_bytes()).next().unwrap().unwrap()
+    }
+
+    fn mapping(entries: &[(&str, &str)]) -> AgentMapping {
+        AgentMapping { agents: entries.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect() }
+    }
+
+    #[test]
+    fn identities_prefer_san_uris_then_dns_names_then_cn() {
+        assert_eq!(certificate_identities(&der(SAN_URI_DNS_CN)).unwrap(), vec![
+            "spiffe://example.org/agents/copilot",
+            "copilot.example.org",
+            "other.example.org",
+            "copilot-cn",
+        ]);
+        assert_eq!(certificate_identities(&der(SAN_DNS_CN)).unwrap(), vec!["copilot.example.org", "copilot-cn"]);
+        assert_eq!(certificate_identities(&der(CN_ONLY)).unwrap(), vec!["copilot-cn"]);
+        assert!(certificate_identities(&der(NO_IDENTITY)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unmapped_certificates_name_their_most_preferred_identity() {
+        let agents = AgentMapping::default();
+        assert_eq!(agents.agent_for_certificate(&der(SAN_URI_DNS_CN)).unwrap(), "spiffe://example.org/agents/copilot");
+        assert_eq!(agents.agent_for_certificate(&der(SAN_DNS_CN)).unwrap(), "copilot.example.org");
+        assert_eq!(agents.agent_for_certificate(&der(CN_ONLY)).unwrap(), "copilot-cn");
+        assert!(agents.agent_for_certificate(&der(NO_IDENTITY)).is_err());
+    }
+
+    #[test]
+    fn client_agents_map_the_most_preferred_listed_identity() {
+        let agents = mapping(&[("copilot-cn", "from-cn"), ("other.example.org", "from-dns")]);
+        assert_eq!(agents.agent_for_certificate(&der(SAN_URI_DNS_CN)).unwrap(), "from-dns");
+        assert_eq!(agents.agent_for_certificate(&der(SAN_DNS_CN)).unwrap(), "from-cn");
+
+        let agents = mapping(&[("copilot-cn", "from-cn"), ("spiffe://example.org/agents/copilot", "copilot")]);
+        assert_eq!(agents.agent_for_certificate(&der(SAN_URI_DNS_CN)).unwrap(), "copilot");
+        assert_eq!(agents.agent_for_certificate(&der(CN_ONLY)).unwrap(), "from-cn");
+    }
+}
@@ -0,0 +1,114 @@
+// src/infrastructure/event_broker.rs
+//! Subscriber for external events published on the event broker.
+//!
+//! Identity providers (CAEP) and the PDP publish JSON notifications on a Redis pub/sub
+//! channel, e.g. `{"type": "session_revoked", "session_id": "<hex>"}`. Each is applied
+//! with the matching command and the resulting domain events are published to the
+//! server's subscribers, which push them to the affected agents.
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use crate::application::commands::{
+    handle_change_context, handle_require_step_up, handle_revoke_token, handle_update_policy,
+    ChangeContextCommand, RequireStepUpCommand, RevokeTokenCommand, UpdatePolicyCommand,
+};
+use crate::application::state::AppState;
+use crate::config::RedisConfig;
+use crate::domain::context::{DevicePosture, RiskLevel};
+use crate::domain::events::DomainEvent;
+
+/// Delay before resubscribing after the broker connection was lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// A notification published on the broker channel.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BrokerEvent {
+    /// The identity provider revoked the session (CAEP session-revoked).
+    SessionRevoked { session_id: String },
+    /// The session's risk level or device posture changed (CAEP risk and
+    /// device-compliance changes).
+    ContextChanged {
+        session_id: String,
+        risk_level: Option<RiskLevel>,
+        device_posture: Option<DevicePosture>,
+    },
+    /// The identity provider requires the session's user to authenticate again.
+    StepUpRequired { session_id: String, reason: Option<String> },
+    /// The PDP activated a new policy set.
+    PolicyUpdated { policy_version: u32 },
+}
+
+/// Subscribes to the broker channel and applies its events until the task is dropped,
+/// resubscribing whenever the connection is lost.
+pub async fn run_event_subscriber(config: RedisConfig, state: Arc<AppState>) -> Result<()> {
+    let client = redis::Client::open(config.url.as_str())?;
+    loop {
+        if let Err(e) = subscribe(&client, &config.events_channel, &state).await {
+            warn!("Event broker subscription failed: {:?}", e);
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn subscribe(client: &redis::Client, channel: &str, state: &AppState) -> Result<()> {
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(channel).await?;
+    info!("Subscribed to event broker channel {}", channel);
+    let mut messages = pubsub.on_message();
+    while let Some(msg) = messages.next().await {
+        let payload: String = match msg.get_payload() {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Ignoring unreadable broker message: {:?}", e);
+                continue;
+            }
+        };
+        let event = match serde_json::from_str::<BrokerEvent>(&payload) {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Ignoring malformed broker event {:?}: {}", payload, e);
+                continue;
+            }
+        };
+        match apply(event, state).await {
+            Ok(events) => state.publish(events),
+            Err(e) => warn!("Failed to apply broker event {:?}: {:?}", payload, e),
+        }
+    }
+    Err(anyhow!("broker connection closed"))
+}
+
+/// Applies a broker event with the matching command.
+pub async fn apply(event: BrokerEvent, state: &AppState) -> Result<Vec<DomainEvent>> {
+    match event {
+        BrokerEvent::SessionRevoked { session_id } => {
+            handle_revoke_token(RevokeTokenCommand { session_id }, state).await
+        }
+        BrokerEvent::ContextChanged { session_id, risk_level, device_posture } => {
+            let cmd = ChangeContextCommand {
+                session_id: parse_session_id(&session_id)?,
+                risk_level,
+                device_posture,
+            };
+            Ok(vec![handle_change_context(cmd, state).await?])
+        }
+        BrokerEvent::StepUpRequired { session_id, reason } => {
+            let cmd = RequireStepUpCommand { session_id: parse_session_id(&session_id)?, reason };
+            Ok(vec![handle_require_step_up(cmd, state).await?])
+        }
+        BrokerEvent::PolicyUpdated { policy_version } => {
+            Ok(vec![handle_update_policy(UpdatePolicyCommand { policy_version }, state).await?])
+        }
+    }
+}
+
+/// Parses a hex-encoded session ID, as carried by broker events and push messages.
+pub(crate) fn parse_session_id(hex_id: &str) -> Result<[u8; 16]> {
+    hex::decode(hex_id)?
+        .try_into()
+        .map_err(|_| anyhow!("Session IDs are 16 bytes"))
+}
@@ -0,0 +1,307 @@
+// src/infrastructure/framing.rs
+//! Framing for messages exchanged on QUIC streams.
+//!
+//! Every message travels in one frame; a stream carries any number of frames in
+//! each direction:
+//!
+//! | Size | Field                                   |
+//! |------|-----------------------------------------|
+//! | 1    | Message type ([`MessageType`])          |
+//! | 4    | Payload length `N` (`u32`, big-endian)  |
+//! | N    | Payload, a JSON document                |
+//!
+//! Requests travel on bidirectional streams and are answered on the same stream;
+//! one-way messages travel on unidirectional streams and are never answered. The
+//! server pushes to agents on unidirectional control streams it opens itself.
+//!
+//! A stream ends cleanly only at a frame boundary. Frames longer than the codec's
+//! maximum are rejected from the length prefix, before the payload is read.
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Length of the type and length prefix.
+pub const FRAME_HEADER_LEN: usize = 5;
+/// Default upper bound on a frame payload.
+pub const MAX_FRAME_LEN: usize = 16 * 1024;
+
+/// Message types; requests are odd, their responses the following even value. Any
+/// request can instead be answered with an [`MessageType::Error`]. One-way messages
+/// start at `0x40`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum MessageType {
+    ChallengeRequest = 0x01,
+    ChallengeResponse = 0x02,
+    ExchangeRequest = 0x03,
+    ExchangeResponse = 0x04,
+    IntrospectionRequest = 0x05,
+    IntrospectionResponse = 0x06,
+    AuthorizeRequest = 0x07,
+    AuthorizeResponse = 0x08,
+    RefreshRequest = 0x09,
+    RefreshResponse = 0x0a,
+    RevocationRequest = 0x0b,
+    RevocationResponse = 0x0c,
+    /// The agent's current device posture.
+    PostureReport = 0x40,
+    /// A server-initiated push on a session's control stream.
+    Push = 0x41,
+    /// The agent's acknowledgement of pushes on a control stream.
+    PushAck = 0x42,
+    /// RFC 6749 §5.2 error response to a failed request.
+    Error = 0xff,
+}
+
+impl MessageType {
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        Some(match byte {
+            0x01 => MessageType::ChallengeRequest,
+            0x02 => MessageType::ChallengeResponse,
+            0x03 => MessageType::ExchangeRequest,
+            0x04 => MessageType::ExchangeResponse,
+            0x05 => MessageType::IntrospectionRequest,
+            0x06 => MessageType::IntrospectionResponse,
+            0x07 => MessageType::AuthorizeRequest,
+            0x08 => MessageType::AuthorizeResponse,
+            0x09 => MessageType::RefreshRequest,
+            0x0a => MessageType::RefreshResponse,
+            0x0b => MessageType::RevocationRequest,
+            0x0c => MessageType::RevocationResponse,
+            0x40 => MessageType::PostureReport,
+            0x41 => MessageType::Push,
+            0x42 => MessageType::PushAck,
+            0xff => MessageType::Error,
+            _ => return None,
+        })
+    }
+}
+
+/// A typed message carried in a frame of its [`MessageType`].
+pub trait Message {
+    const TYPE: MessageType;
+}
+
+/// Reasons a frame cannot be read or written.
+#[derive(Debug, Error)]
+pub enum FrameError {
+    #[error("stream error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("stream ended inside a frame")]
+    Truncated,
+    #[error("frame of {len} bytes exceeds the {max} byte limit")]
+    TooLarge { len: usize, max: usize },
+    #[error("unknown message type {0:#04x}")]
+    UnknownType(u8),
+    #[error("expected a {expected:?} message, got {actual:?}")]
+    UnexpectedType { expected: MessageType, actual: MessageType },
+    #[error("{0:?} messages are not accepted here")]
+    NotAccepted(MessageType),
+    #[error("invalid message payload: {0}")]
+    Payload(#[from] serde_json::Error),
+}
+
+/// A message type and its still-encoded payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: MessageType,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Encodes `message` as the payload of a frame of its type.
+    pub fn encode<M: Message + Serialize>(message: &M) -> Result<Self, FrameError> {
+        Ok(Self { kind: M::TYPE, payload: serde_json::to_vec(message)? })
+    }
+
+    /// Decodes the payload as `M`, which must match the frame's type.
+    pub fn decode<M: Message + DeserializeOwned>(&self) -> Result<M, FrameError> {
+        if self.kind != M::TYPE {
+            return Err(FrameError::UnexpectedType { expected: M::TYPE, actual: self.kind });
+        }
+        Ok(serde_json::from_slice(&self.payload)?)
+    }
+}
+
+/// Reads and writes frames on the halves of a stream, such as quinn's
+/// `RecvStream` and `SendStream`.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameCodec {
+    max_frame_len: usize,
+}
+
+impl Default for FrameCodec {
+    fn default() -> Self {
+        Self::new(MAX_FRAME_LEN)
+    }
+}
+
+impl FrameCodec {
+    pub fn new(max_frame_len: usize) -> Self {
+        Self { max_frame_len }
+    }
+
+    /// Reads the next frame; `None` if the stream ended cleanly before it.
+    pub async fn read_frame<R: AsyncRead + Unpin>(&self, recv: &mut R) -> Result<Option<Frame>, FrameError> {
+        let mut header = [0u8; FRAME_HEADER_LEN];
+        if recv.read(&mut header[..1]).await? == 0 {
+            return Ok(None);
+        }
+        read_exact(recv, &mut header[1..]).await?;
+        let kind = MessageType::from_byte(header[0]).ok_or(FrameError::UnknownType(header[0]))?;
+        let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        if len > self.max_frame_len {
+            return Err(FrameError::TooLarge { len, max: self.max_frame_len });
+        }
+        let mut payload = vec![0u8; len];
+        read_exact(recv, &mut payload).await?;
+        Ok(Some(Frame { kind, payload }))
+    }
+
+    /// Reads the next frame as an `M`; `None` if the stream ended cleanly before it.
+    pub async fn read_message<M, R>(&self, recv: &mut R) -> Result<Option<M>, FrameError>
+    where
+        M: Message + DeserializeOwned,
+        R: AsyncRead + Unpin,
+    {
+        self.read_frame(recv).await?.map(|frame| frame.decode()).transpose()
+    }
+
+    pub async fn write_frame<W: AsyncWrite + Unpin>(&self, send: &mut W, frame: &Frame) -> Result<(), FrameError> {
+        let len = frame.payload.len();
+        if len > self.max_frame_len {
+            return Err(FrameError::TooLarge { len, max: self.max_frame_len });
+        }
+        let mut out = Vec::with_capacity(FRAME_HEADER_LEN + len);
+        out.push(frame.kind as u8);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+        out.extend_from_slice(&frame.payload);
+        send.write_all(&out).await?;
+        Ok(())
+    }
+
+    pub async fn write_message<M, W>(&self, send: &mut W, message: &M) -> Result<(), FrameError>
+    where
+        M: Message + Serialize,
+        W: AsyncWrite + Unpin,
+    {
+        self.write_frame(send, &Frame::encode(message)?).await
+    }
+}
+
+/// Fills `buf`, reporting a stream that ends first as a truncated frame.
+async fn read_exact<R: AsyncRead + Unpin>(recv: &mut R, buf: &mut [u8]) -> Result<(), FrameError> {
+    match recv.read_exact(buf).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Err(FrameError::Truncated),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use tokio_test::io::Builder;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Report {
+        status: String,
+    }
+
+    impl Message for Report {
+        const TYPE: MessageType = MessageType::PostureReport;
+    }
+
+    fn header(kind: u8, len: u32) -> Vec<u8> {
+        let mut header = vec![kind];
+        header.extend_from_slice(&len.to_be_bytes());
+        header
+    }
+
+    #[tokio::test]
+    async fn reads_frames_split_across_reads() {
+        let payload = br#"{"status":"ok"}"#;
+        let mut recv = Builder::new()
+            .read(&[0x40])
+            .read(&header(0x40, payload.len() as u32)[1..3])
+            .read(&header(0x40, payload.len() as u32)[3..])
+            .read(&payload[..4])
+            .read(&payload[4..])
+            .build();
+        let codec = FrameCodec::default();
+        let report: Report = codec.read_message(&mut recv).await.unwrap().unwrap();
+        assert_eq!(report, Report { status: "ok".into() });
+        assert!(codec.read_frame(&mut recv).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn reads_frames_sharing_one_read() {
+        let mut bytes = Vec::new();
+        let codec = FrameCodec::default();
+        codec.write_message(&mut bytes, &Report { status: "a".into() }).await.unwrap();
+        codec.write_frame(&mut bytes, &Frame { kind: MessageType::PushAck, payload: Vec::new() }).await.unwrap();
+        let mut recv = Builder::new().read(&bytes).build();
+
+        let first = codec.read_frame(&mut recv).await.unwrap().unwrap();
+        assert_eq!(first.decode::<Report>().unwrap(), Report { status: "a".into() });
+        let second = codec.read_frame(&mut recv).await.unwrap().unwrap();
+        assert_eq!(second, Frame { kind: MessageType::PushAck, payload: Vec::new() });
+        assert!(codec.read_frame(&mut recv).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_length_before_reading_payload() {
+        let codec = FrameCodec::new(8);
+        // No payload follows: the frame must be rejected from its prefix alone.
+        let mut recv = Builder::new().read(&header(0x40, 9)).build();
+        let err = codec.read_frame(&mut recv).await.unwrap_err();
+        assert!(matches!(err, FrameError::TooLarge { len: 9, max: 8 }), "{err:?}");
+
+        let mut recv = Builder::new().read(&header(0x40, u32::MAX)).build();
+        let err = FrameCodec::default().read_frame(&mut recv).await.unwrap_err();
+        assert!(matches!(err, FrameError::TooLarge { len, .. } if len == u32::MAX as usize), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn refuses_to_write_oversized_frames() {
+        let codec = FrameCodec::new(8);
+        let mut out = Vec::new();
+        let frame = Frame { kind: MessageType::Push, payload: vec![b'0'; 9] };
+        let err = codec.write_frame(&mut out, &frame).await.unwrap_err();
+        assert!(matches!(err, FrameError::TooLarge { len: 9, max: 8 }), "{err:?}");
+        assert!(out.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_type_bytes() {
+        for byte in [0x00, 0x0d, 0x3f, 0x43, 0xfe] {
+            let mut recv = Builder::new().read(&header(byte, 0)).build();
+            let err = FrameCodec::default().read_frame(&mut recv).await.unwrap_err();
+            assert!(matches!(err, FrameError::UnknownType(b) if b == byte), "{err:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_streams_ending_inside_a_frame() {
+        let mut recv = Builder::new().read(&header(0x40, 4)[..3]).build();
+        let err = FrameCodec::default().read_frame(&mut recv).await.unwrap_err();
+        assert!(matches!(err, FrameError::Truncated), "{err:?}");
+
+        let mut recv = Builder::new().read(&header(0x40, 4)).read(b"{}").build();
+        let err = FrameCodec::default().read_frame(&mut recv).await.unwrap_err();
+        assert!(matches!(err, FrameError::Truncated), "{err:?}");
+    }
+
+    #[test]
+    fn decode_checks_the_frame_type() {
+        let frame = Frame { kind: MessageType::Push, payload: br#"{"status":"ok"}"#.to_vec() };
+        let err = frame.decode::<Report>().unwrap_err();
+        assert!(matches!(
+            err,
+            FrameError::UnexpectedType { expected: MessageType::PostureReport, actual: MessageType::Push }
+        ), "{err:?}");
+        let frame = Frame { kind: MessageType::PostureReport, payload: b"{".to_vec() };
+        assert!(matches!(frame.decode::<Report>(), Err(FrameError::Payload(_))));
+    }
+}
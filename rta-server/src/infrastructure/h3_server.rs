@@ -0,0 +1,197 @@
+// src/infrastructure/h3_server.rs
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::{Buf, BufMut, Bytes};
+use h3::server::RequestStream;
+use http::header::{ALLOW, CACHE_CONTROL, CONTENT_TYPE, WWW_AUTHENTICATE};
+use http::{HeaderValue, Method, Request, Response, StatusCode};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::application::state::AppState;
+use crate::domain::clock::unix_now;
+use crate::infrastructure::client_auth::AgentMapping;
+use crate::infrastructure::quic_server::{
+    confirm_handshake, exchange_token, ErrorResponse, ExchangeErrorCode, RequestError, TokenExchangeRequest,
+};
+use crate::infrastructure::quic_session::ConnectionSession;
+
+/// ALPN protocol of HTTP/3 (RFC 9114).
+pub const ALPN_H3: &[u8] = b"h3";
+
+/// Response header carrying a fresh nonce when a token request lacked a valid one.
+pub const NONCE_HEADER: &str = "rta-nonce";
+
+/// Upper bound on request bodies; token requests carry at most two tokens.
+const MAX_BODY_LEN: usize = 16 * 1024;
+
+type H3Stream = RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>;
+
+/// Serves the HTTP/3 token endpoint on a connection that negotiated `h3`:
+///
+/// - `POST /token`: form-encoded token request, answered with the same JSON as an
+///   exchange response of the framed protocol. The form carries the members of the
+///   framed exchange request, except that scopes are space-delimited in `scope`.
+///
+/// Requests are served concurrently. As in the framed protocol, every exchange must
+/// redeem a nonce issued on the same connection; a request without a valid nonce is
+/// rejected with a fresh one in the `RTA-Nonce` header, in the manner of DPoP server
+/// nonces (RFC 9449 §8). A client certificate's `agent_id` is enforced as on the
+/// framed protocol. The connection's handshake must be complete.
+pub async fn handle_h3_connection(
+    conn: quinn::Connection,
+    agents: &AgentMapping,
+    state: Arc<AppState>,
+) -> Result<()> {
+    let session = Arc::new(ConnectionSession::new(conn.clone()));
+    confirm_handshake(&session, agents)?;
+    let mut h3_conn = h3::server::Connection::new(h3_quinn::Connection::new(conn)).await?;
+    loop {
+        match h3_conn.accept().await {
+            Ok(Some(resolver)) => {
+                let session = session.clone();
+                let state = state.clone();
+                tokio::spawn(async move {
+                    match resolver.resolve_request().await {
+                        Ok((req, stream)) => serve_request(req, stream, &session, &state).await,
+                        Err(e) => error!("Failed to read HTTP/3 request from {}: {:?}", session.client_ip(), e),
+                    }
+                });
+            }
+            Ok(None) => return Ok(()),
+            Err(e) if e.is_h3_no_error() => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+async fn serve_request(req: Request<()>, mut stream: H3Stream, session: &ConnectionSession, state: &AppState) {
+    let (resp, body) = match (req.method(), req.uri().path()) {
+        (&Method::POST, "/token") => token(&req, &mut stream, session, state).await,
+        (_, "/token") => {
+            let mut resp = status(StatusCode::METHOD_NOT_ALLOWED);
+            resp.headers_mut().insert(ALLOW, HeaderValue::from_static("POST"));
+            (resp, None)
+        }
+        _ => (status(StatusCode::NOT_FOUND), None),
+    };
+    if let Err(e) = send(&mut stream, resp, body).await {
+        error!("Failed to send HTTP/3 response to {}: {:?}", session.client_ip(), e);
+    }
+}
+
+/// Answers a token request with the response status and JSON body.
+async fn token(
+    req: &Request<()>,
+    stream: &mut H3Stream,
+    session: &ConnectionSession,
+    state: &AppState,
+) -> (Response<()>, Option<Vec<u8>>) {
+    let result = match read_form(req, stream).await {
+        Ok(form) => exchange_token(session, form, state).await,
+        Err(e) => Err(e),
+    };
+    let err = match result.and_then(|resp| Ok(serde_json::to_vec(&resp)?)) {
+        Ok(body) => return (json(StatusCode::OK), Some(body)),
+        Err(e) => e,
+    };
+
+    error!("HTTP/3 token request from {} failed: {:?}", session.client_ip(), err);
+    let code = ExchangeErrorCode::for_error(&err);
+    let error = ErrorResponse::for_error(code, &err);
+    let mut resp = json(match code {
+        ExchangeErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        ExchangeErrorCode::ClientAuthFailed | ExchangeErrorCode::AgentMismatch => StatusCode::UNAUTHORIZED,
+        _ => StatusCode::BAD_REQUEST,
+    });
+    // RFC 6749 §5.2: a 401 response must carry a challenge.
+    if resp.status() == StatusCode::UNAUTHORIZED {
+        resp.headers_mut().insert(WWW_AUTHENTICATE, HeaderValue::from_static("Basic realm=\"rta\""));
+    }
+    if matches!(code, ExchangeErrorCode::NonceReplayed | ExchangeErrorCode::NonceExpired) {
+        match issue_nonce(session, state) {
+            Ok(nonce) => {
+                resp.headers_mut().insert(NONCE_HEADER, nonce);
+            }
+            Err(e) => error!("Failed to issue a nonce to {}: {:?}", session.client_ip(), e),
+        }
+    }
+    (resp, serde_json::to_vec(&error).ok())
+}
+
+/// Reads and parses a form-encoded request body of at most [`MAX_BODY_LEN`] bytes.
+async fn read_form(req: &Request<()>, stream: &mut H3Stream) -> Result<TokenExchangeRequest> {
+    let form_encoded = req.headers().get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(';').next().unwrap_or("").trim() == "application/x-www-form-urlencoded");
+    if !form_encoded {
+        return Err(RequestError::Invalid("token requests must be application/x-www-form-urlencoded".into()).into());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = stream.recv_data().await? {
+        if body.len() + chunk.remaining() > MAX_BODY_LEN {
+            return Err(RequestError::Invalid(format!("request body exceeds {} bytes", MAX_BODY_LEN)).into());
+        }
+        body.put(chunk);
+    }
+    serde_urlencoded::from_bytes(&body).map_err(|e| RequestError::Invalid(e.to_string()).into())
+}
+
+/// Issues a single-use nonce bound to the connection, as a header value.
+fn issue_nonce(session: &ConnectionSession, state: &AppState) -> Result<HeaderValue> {
+    let nonce = state.nonces.issue(session.binding_hash(), unix_now())?;
+    Ok(HeaderValue::from_str(&STANDARD.encode(nonce))?)
+}
+
+async fn send(stream: &mut H3Stream, resp: Response<()>, body: Option<Vec<u8>>) -> Result<()> {
+    stream.send_response(resp).await?;
+    if let Some(body) = body {
+        stream.send_data(Bytes::from(body)).await?;
+    }
+    stream.finish().await?;
+    Ok(())
+}
+
+/// A JSON response that must not be cached, as token responses (RFC 6749 §5.1).
+fn json(code: StatusCode) -> Response<()> {
+    let mut resp = status(code);
+    resp.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    resp.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    resp
+}
+
+fn status(code: StatusCode) -> Response<()> {
+    let mut resp = Response::new(());
+    *resp.status_mut() = code;
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::context::DevicePosture;
+
+    #[test]
+    fn token_form_parses_as_exchange_request() {
+        let form = "grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Atoken-exchange&nonce=bm9uY2U%3D\
+                    &agent_id=agent-7&device_posture=compliant&scope=files.read+files.write\
+                    &max_depth=2&attenuable=true";
+        let req: TokenExchangeRequest = serde_urlencoded::from_str(form).unwrap();
+        assert_eq!(req.grant_type, "urn:ietf:params:oauth:grant-type:token-exchange");
+        assert_eq!(req.nonce.as_deref(), Some("bm9uY2U="));
+        assert_eq!(req.agent_id, "agent-7");
+        assert_eq!(req.device_posture, DevicePosture::Compliant);
+        assert_eq!(req.scope.as_deref(), Some("files.read files.write"));
+        assert_eq!(req.scopes, None);
+        assert_eq!(req.max_depth, 2);
+        assert!(req.attenuable);
+        assert!(!req.jwt);
+        assert!(req.oauth_token.is_empty());
+    }
+
+    #[test]
+    fn token_form_rejects_scopes_list() {
+        assert!(serde_urlencoded::from_str::<TokenExchangeRequest>("grant_type=x&scopes=files.read").is_err());
+    }
+}
@@ -0,0 +1,208 @@
+// src/infrastructure/http_server.rs
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hyper::body::HttpBody;
+use hyper::header::{
+    AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH, WWW_AUTHENTICATE,
+};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use ring::digest;
+use crate::application::queries::{authenticate_client, introspect_token, verification_key_set};
+use crate::application::state::AppState;
+use crate::config::HttpConfig;
+use crate::domain::token;
+use rta_verify::caveat::AccessRequest;
+
+/// Upper bound on request bodies; introspection requests are a single token.
+const MAX_BODY_LEN: usize = 8 * 1024;
+
+/// Form parameters of an introspection request (RFC 7662 §2.1).
+///
+/// Besides `token`, resource servers may describe the access they are about to grant
+/// so that caveats of attenuated tokens can be evaluated.
+#[derive(Debug, Deserialize)]
+struct IntrospectionForm {
+    /// Base64-encoded RTAToken.
+    token: String,
+    resource: Option<String>,
+    action: Option<String>,
+    client_ip: Option<std::net::IpAddr>,
+}
+
+/// Runs the plain HTTP endpoints:
+///
+/// - `POST /introspect`: RFC 7662 token introspection, HTTP Basic client authentication.
+/// - `GET /.well-known/jwks.json`: token verification keys as a JWK Set.
+/// - `GET /metrics`: QUIC connection and resumption counters for Prometheus.
+pub async fn run_http_server(addr: SocketAddr, config: &HttpConfig, state: Arc<AppState>) -> Result<()> {
+    let jwks_max_age_secs = config.jwks_max_age_secs;
+    let make_svc = make_service_fn(move |_conn| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let state = state.clone();
+                async move { Ok::<_, Infallible>(route(req, &state, jwks_max_age_secs).await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)?.serve(make_svc);
+    info!("HTTP endpoint listening on {}", addr);
+    server.await?;
+    Ok(())
+}
+
+async fn route(req: Request<Body>, state: &AppState, jwks_max_age_secs: u64) -> Response<Body> {
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/.well-known/jwks.json") => match jwks(&req, state, jwks_max_age_secs) {
+            Ok(resp) => resp,
+            Err(e) => {
+                error!("Failed to publish verification keys: {:?}", e);
+                status(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        },
+        (_, "/.well-known/jwks.json") => status(StatusCode::METHOD_NOT_ALLOWED),
+        (&Method::POST, "/introspect") => match introspect(req, state).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                error!("Introspection request failed: {:?}", e);
+                status(StatusCode::BAD_REQUEST)
+            }
+        },
+        (_, "/introspect") => status(StatusCode::METHOD_NOT_ALLOWED),
+        (&Method::GET, "/metrics") => Response::builder()
+            .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(Body::from(state.metrics.render()))
+            .unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR)),
+        (_, "/metrics") => status(StatusCode::METHOD_NOT_ALLOWED),
+        _ => status(StatusCode::NOT_FOUND),
+    }
+}
+
+async fn introspect(req: Request<Body>, state: &AppState) -> Result<Response<Body>> {
+    let authenticated = basic_credentials(&req)
+        .is_some_and(|(id, secret)| authenticate_client(state, &id, &secret).is_ok());
+    if !authenticated {
+        let mut resp = status(StatusCode::UNAUTHORIZED);
+        resp.headers_mut().insert(WWW_AUTHENTICATE, "Basic realm=\"rta\"".parse()?);
+        return Ok(resp);
+    }
+
+    let Some(body) = read_body(req.into_body()).await? else {
+        return Ok(status(StatusCode::PAYLOAD_TOO_LARGE));
+    };
+    let form: IntrospectionForm = serde_urlencoded::from_bytes(&body)?;
+    let request = AccessRequest {
+        resource: form.resource.as_deref(),
+        action: form.action.as_deref(),
+        client_ip: form.client_ip,
+    };
+    // A token that is not even Base64 is simply inactive.
+    let encoded = token::decode_base64(form.token.trim()).unwrap_or_default();
+    let introspection = introspect_token(&encoded, state, &request);
+
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_vec(&introspection)?))?)
+}
+
+/// Serves the verification key set.
+///
+/// The set is cacheable for `max_age_secs`; its ETag changes whenever a key is added
+/// or retired, so caches can revalidate cheaply with `If-None-Match`.
+fn jwks(req: &Request<Body>, state: &AppState, max_age_secs: u64) -> Result<Response<Body>> {
+    let body = serde_json::to_vec(&verification_key_set(state))?;
+    let etag = format!("\"{}\"", hex::encode(&digest::digest(&digest::SHA256, &body).as_ref()[..16]));
+    let cache_control = format!("public, max-age={}", max_age_secs);
+
+    let not_modified = req.headers().get(IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|tags| tags.split(',').any(|t| t.trim() == etag || t.trim() == "*"));
+    let builder = Response::builder()
+        .header(ETAG, &etag)
+        .header(CACHE_CONTROL, cache_control);
+    if not_modified {
+        return Ok(builder.status(StatusCode::NOT_MODIFIED).body(Body::empty())?);
+    }
+    Ok(builder
+        .header(CONTENT_TYPE, "application/jwk-set+json")
+        .body(Body::from(body))?)
+}
+
+/// Reads a request body of at most [`MAX_BODY_LEN`] bytes; `None` if it is longer.
+async fn read_body(mut body: Body) -> Result<Option<Vec<u8>>> {
+    let mut buf = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if buf.len() + chunk.len() > MAX_BODY_LEN {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(Some(buf))
+}
+
+/// Client ID and secret from an `Authorization: Basic` header.
+///
+/// Both are form-urlencoded before they are joined and Base64-encoded (RFC 6749
+/// §2.3.1), so a secret may contain a `:`.
+fn basic_credentials(req: &Request<Body>) -> Option<(String, String)> {
+    let value = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (id, secret) = decoded.split_once(':')?;
+    Some((form_urldecode(id)?, form_urldecode(secret)?))
+}
+
+/// Decodes an `application/x-www-form-urlencoded` value.
+fn form_urldecode(value: &str) -> Option<String> {
+    let value = value.replace('+', " ");
+    percent_decode_str(&value).decode_utf8().ok().map(|s| s.into_owned())
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = code;
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn basic(credentials: &str) -> Request<Body> {
+        Request::builder()
+            .header(AUTHORIZATION, format!("Basic {}", STANDARD.encode(credentials)))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn credentials(id: &str, secret: &str) -> Option<(String, String)> {
+        Some((id.to_string(), secret.to_string()))
+    }
+
+    #[test]
+    fn basic_credentials_are_form_urldecoded() {
+        assert_eq!(basic_credentials(&basic("files:s3cret")), credentials("files", "s3cret"));
+        assert_eq!(basic_credentials(&basic("my+client:p%3Ass%25w+rd")), credentials("my client", "p:ss%w rd"));
+        assert_eq!(basic_credentials(&basic("caf%C3%A9:%2B")), credentials("café", "+"));
+    }
+
+    #[test]
+    fn malformed_basic_credentials_are_rejected() {
+        assert_eq!(basic_credentials(&basic("no-separator")), None);
+        assert_eq!(basic_credentials(&basic("files:%FF")), None);
+        let bearer = Request::builder().header(AUTHORIZATION, "Bearer abc").body(Body::empty()).unwrap();
+        assert_eq!(basic_credentials(&bearer), None);
+        assert_eq!(basic_credentials(&Request::new(Body::empty())), None);
+    }
+}
@@ -4,9 +4,21 @@ use reqwest::Client;
 use serde_json::Value;
 use crate::config::IdpProviders;
 
+/// Claims taken from a successful introspection response.
+#[derive(Debug, Clone, Default)]
+pub struct Introspection {
+    /// The provider that vouched for the token (e.g. "azure").
+    pub provider: String,
+    pub subject: Option<String>,
+    pub scopes: Vec<String>,
+}
+
 /// Introspects the provided OAuth token using the configuration for the selected provider.
 /// If `provider` is None, the default provider is used.
-pub async fn introspect(oauth_token: &str, provider: Option<&str>, providers: &IdpProviders) -> Result<bool> {
+///
+/// Returns `None` unless the provider answers as an RFC 7662 introspection endpoint
+/// and reports the token `active`; a response that cannot be parsed is an error.
+pub async fn introspect(oauth_token: &str, provider: Option<&str>, providers: &IdpProviders) -> Result<Option<Introspection>> {
     // Select the provider – use the provided value, or fall back to the default.
     let selected = provider.unwrap_or(&providers.default).to_lowercase();
     let idp_config = match selected.as_str() {
@@ -23,15 +35,29 @@ pub async fn introspect(oauth_token: &str, provider: Option<&str>, providers: &I
         ("client_id", &idp_config.client_id),
         ("client_secret", &idp_config.client_secret),
     ];
-    
+
     // Send the introspection request.
     let resp = client.post(&idp_config.introspection_url)
         .form(&params)
         .send()
         .await?;
-    let status = resp.status();
+    if !resp.status().is_success() {
+        return Ok(None);
+    }
+
+    // Fail closed: only an explicit `"active": true` vouches for the token.
+    let json: Value = resp.json().await
+        .map_err(|e| anyhow::anyhow!("Invalid introspection response from {}: {}", selected, e))?;
+    if json.get("active").and_then(Value::as_bool) != Some(true) {
+        return Ok(None);
+    }
 
-    // Parse the response body as JSON (optional).
-    let _json: Value = resp.json().await.unwrap_or(Value::Null);
-    Ok(status.is_success())
+    let subject = json.get("sub").and_then(Value::as_str).map(str::to_owned);
+    // RFC 7662 uses a space-separated `scope`; Azure AD uses `scp`.
+    let scopes = json.get("scope")
+        .or_else(|| json.get("scp"))
+        .and_then(Value::as_str)
+        .map(|s| s.split_whitespace().map(str::to_owned).collect())
+        .unwrap_or_default();
+    Ok(Some(Introspection { provider: selected, subject, scopes }))
 }
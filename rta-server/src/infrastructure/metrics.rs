@@ -0,0 +1,233 @@
+// src/infrastructure/metrics.rs
+//! Counters of the QUIC listener, served in the Prometheus text format on the HTTP
+//! endpoint's `/metrics`.
+//!
+//! Tickets are counted in the TLS session store: every ticket handed out to a client
+//! is stored, and redeemed (and removed) when the client resumes with it. A
+//! resumption is counted only once the handshake that redeemed the ticket has
+//! completed, by [`CountingServerConfig`], so that replayed or abandoned ClientHellos
+//! do not count. The resumption rate is the ratio of resumptions to accepted
+//! connections.
+use quinn::crypto::rustls::QuicServerConfig;
+use quinn::crypto::{self, ExportKeyingMaterialError, HeaderKey, KeyPair, Keys, PacketKey, UnsupportedVersion};
+use quinn::{ConnectionId, Side};
+use quinn_proto::transport_parameters::TransportParameters;
+use quinn_proto::TransportError;
+use rustls::server::{ServerSessionMemoryCache, StoresServerSessions};
+use std::any::Any;
+use std::cell::Cell;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+thread_local! {
+    /// Whether the session store redeemed a ticket on this thread since it was last
+    /// cleared. rustls looks tickets up while processing a ClientHello, within the
+    /// [`crypto::Session::read_handshake`] call of the connection it belongs to.
+    static REDEEMED: Cell<bool> = const { Cell::new(false) };
+}
+
+#[derive(Debug, Default)]
+pub struct TransportMetrics {
+    connections: AtomicU64,
+    tickets_issued: AtomicU64,
+    resumptions: AtomicU64,
+    /// Replay-safe requests in early data, served without waiting for the handshake.
+    early_requests: AtomicU64,
+    /// Requests in early data that waited for the handshake to be confirmed.
+    deferred_requests: AtomicU64,
+}
+
+impl TransportMetrics {
+    pub fn connection_accepted(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a request received in early data, before the handshake was confirmed.
+    pub fn early_request(&self, replay_safe: bool) {
+        let counter = if replay_safe { &self.early_requests } else { &self.deferred_requests };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The counters in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let counters = [
+            ("rta_quic_connections_total", "QUIC connections accepted.", None, &self.connections),
+            ("rta_quic_tickets_issued_total", "TLS resumption tickets issued.", None, &self.tickets_issued),
+            ("rta_quic_resumptions_total", "Completed handshakes that redeemed a resumption ticket.", None, &self.resumptions),
+            (
+                "rta_quic_early_requests_total",
+                "Requests received in 0-RTT early data.",
+                Some("class=\"replay_safe\""),
+                &self.early_requests,
+            ),
+            (
+                "rta_quic_early_requests_total",
+                "Requests received in 0-RTT early data.",
+                Some("class=\"deferred\""),
+                &self.deferred_requests,
+            ),
+        ];
+        let mut out = String::new();
+        let mut described = None;
+        for (name, help, labels, counter) in counters {
+            if described != Some(name) {
+                let _ = writeln!(out, "# HELP {} {}", name, help);
+                let _ = writeln!(out, "# TYPE {} counter", name);
+                described = Some(name);
+            }
+            let labels = labels.map(|l| format!("{{{}}}", l)).unwrap_or_default();
+            let _ = writeln!(out, "{}{} {}", name, labels, counter.load(Ordering::Relaxed));
+        }
+        out
+    }
+}
+
+/// In-memory TLS session store that counts issued tickets and flags redeemed ones to
+/// [`CountingServerConfig`].
+///
+/// Tickets are taken out of the store when redeemed, so each can resume one
+/// connection only; a replayed ClientHello finds its ticket gone and falls back to a
+/// full handshake without early data.
+#[derive(Debug)]
+pub struct CountingSessionStore {
+    cache: Arc<ServerSessionMemoryCache>,
+    metrics: Arc<TransportMetrics>,
+}
+
+impl CountingSessionStore {
+    pub fn new(size: usize, metrics: Arc<TransportMetrics>) -> Self {
+        Self { cache: ServerSessionMemoryCache::new(size), metrics }
+    }
+
+    fn redeemed(&self, value: Option<Vec<u8>>) -> Option<Vec<u8>> {
+        if value.is_some() {
+            REDEEMED.with(|redeemed| redeemed.set(true));
+        }
+        value
+    }
+}
+
+impl StoresServerSessions for CountingSessionStore {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        let stored = self.cache.put(key, value);
+        if stored {
+            self.metrics.tickets_issued.fetch_add(1, Ordering::Relaxed);
+        }
+        stored
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.redeemed(self.cache.get(key))
+    }
+
+    fn take(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.redeemed(self.cache.take(key))
+    }
+
+    fn can_cache(&self) -> bool {
+        self.cache.can_cache()
+    }
+}
+
+/// QUIC crypto configuration that counts a resumption once a handshake that redeemed
+/// a ticket from [`CountingSessionStore`] completes.
+pub struct CountingServerConfig {
+    inner: Arc<QuicServerConfig>,
+    metrics: Arc<TransportMetrics>,
+}
+
+impl CountingServerConfig {
+    pub fn new(inner: QuicServerConfig, metrics: Arc<TransportMetrics>) -> Self {
+        Self { inner: Arc::new(inner), metrics }
+    }
+}
+
+impl crypto::ServerConfig for CountingServerConfig {
+    fn initial_keys(&self, version: u32, dst_cid: &ConnectionId) -> Result<Keys, UnsupportedVersion> {
+        self.inner.initial_keys(version, dst_cid)
+    }
+
+    fn retry_tag(&self, version: u32, orig_dst_cid: &ConnectionId, packet: &[u8]) -> [u8; 16] {
+        self.inner.retry_tag(version, orig_dst_cid, packet)
+    }
+
+    fn start_session(self: Arc<Self>, version: u32, params: &TransportParameters) -> Box<dyn crypto::Session> {
+        Box::new(CountingSession {
+            inner: self.inner.clone().start_session(version, params),
+            metrics: self.metrics.clone(),
+            redeemed: false,
+            counted: false,
+        })
+    }
+}
+
+/// A server handshake that remembers whether it redeemed a ticket.
+struct CountingSession {
+    inner: Box<dyn crypto::Session>,
+    metrics: Arc<TransportMetrics>,
+    redeemed: bool,
+    counted: bool,
+}
+
+impl crypto::Session for CountingSession {
+    fn initial_keys(&self, dst_cid: &ConnectionId, side: Side) -> Keys {
+        self.inner.initial_keys(dst_cid, side)
+    }
+
+    fn handshake_data(&self) -> Option<Box<dyn Any>> {
+        self.inner.handshake_data()
+    }
+
+    fn peer_identity(&self) -> Option<Box<dyn Any>> {
+        self.inner.peer_identity()
+    }
+
+    fn early_crypto(&self) -> Option<(Box<dyn HeaderKey>, Box<dyn PacketKey>)> {
+        self.inner.early_crypto()
+    }
+
+    fn early_data_accepted(&self) -> Option<bool> {
+        self.inner.early_data_accepted()
+    }
+
+    fn is_handshaking(&self) -> bool {
+        self.inner.is_handshaking()
+    }
+
+    fn read_handshake(&mut self, buf: &[u8]) -> Result<bool, TransportError> {
+        REDEEMED.with(|redeemed| redeemed.set(false));
+        let result = self.inner.read_handshake(buf);
+        self.redeemed |= REDEEMED.with(Cell::take);
+        if self.redeemed && !self.counted && result.is_ok() && !self.inner.is_handshaking() {
+            self.counted = true;
+            self.metrics.resumptions.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn transport_parameters(&self) -> Result<Option<TransportParameters>, TransportError> {
+        self.inner.transport_parameters()
+    }
+
+    fn write_handshake(&mut self, buf: &mut Vec<u8>) -> Option<Keys> {
+        self.inner.write_handshake(buf)
+    }
+
+    fn next_1rtt_keys(&mut self) -> Option<KeyPair<Box<dyn PacketKey>>> {
+        self.inner.next_1rtt_keys()
+    }
+
+    fn is_valid_retry(&self, orig_dst_cid: &ConnectionId, header: &[u8], payload: &[u8]) -> bool {
+        self.inner.is_valid_retry(orig_dst_cid, header, payload)
+    }
+
+    fn export_keying_material(
+        &self,
+        output: &mut [u8],
+        label: &[u8],
+        context: &[u8],
+    ) -> Result<(), ExportKeyingMaterialError> {
+        self.inner.export_keying_material(output, label, context)
+    }
+}
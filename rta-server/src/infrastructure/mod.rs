@@ -1,5 +1,17 @@
 // src/infrastructure/mod.rs
+pub mod client_auth;
+pub mod event_broker;
 pub mod idp_adapter;
+pub mod key_store;
+pub mod metrics;
+pub mod nonce_cache;
 pub mod pdp_adapter;
 pub mod redis_repository;
+pub mod session_store;
+pub mod standard_exchange;
+pub mod framing;
+pub mod push;
+pub mod h3_server;
 pub mod quic_server;
+pub mod quic_session;
+pub mod http_server;
@@ -0,0 +1,191 @@
+// src/infrastructure/nonce_cache.rs
+use dashmap::DashMap;
+use ring::rand::{SecureRandom, SystemRandom};
+use thiserror::Error;
+
+pub const NONCE_LEN: usize = 16;
+
+/// Reasons an exchange nonce is rejected.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum NonceError {
+    /// Never issued, already purged, or issued to another connection.
+    #[error("unknown nonce")]
+    Unknown,
+    #[error("nonce already used")]
+    Replayed,
+    #[error("nonce expired")]
+    Expired,
+    /// The connection already holds the maximum number of unredeemed challenges.
+    #[error("too many outstanding challenges on this connection")]
+    TooMany,
+}
+
+#[derive(Debug)]
+struct NonceEntry {
+    /// Identifies the connection the challenge was issued on.
+    connection: [u8; 32],
+    expires_at: u64,
+    used: bool,
+}
+
+/// In-memory cache of exchange challenges.
+///
+/// Each nonce is single-use and only valid on the connection it was issued on.
+/// Used nonces stay in the cache until they expire so that a replay is reported as
+/// such rather than as an unknown nonce. A connection may hold at most
+/// `max_per_connection` unredeemed nonces at a time; one that expires unredeemed
+/// counts against it until it is purged.
+#[derive(Debug)]
+pub struct NonceCache {
+    entries: DashMap<[u8; NONCE_LEN], NonceEntry>,
+    /// Unredeemed nonces per connection.
+    outstanding: DashMap<[u8; 32], usize>,
+    ttl_secs: u64,
+    max_per_connection: usize,
+    rng: SystemRandom,
+}
+
+impl NonceCache {
+    pub fn new(ttl_secs: u64, max_per_connection: usize) -> Self {
+        Self {
+            entries: DashMap::new(),
+            outstanding: DashMap::new(),
+            ttl_secs,
+            max_per_connection,
+            rng: SystemRandom::new(),
+        }
+    }
+
+    pub fn ttl_secs(&self) -> u64 {
+        self.ttl_secs
+    }
+
+    /// Issues a fresh nonce for `connection`, valid until `now + ttl_secs`.
+    pub fn issue(&self, connection: [u8; 32], now: u64) -> anyhow::Result<[u8; NONCE_LEN]> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng.fill(&mut nonce)?;
+        // The count stays locked until the entry is in, so concurrent challenges on one
+        // connection cannot overshoot the cap.
+        let mut outstanding = self.outstanding.entry(connection).or_insert(0);
+        if *outstanding >= self.max_per_connection {
+            return Err(NonceError::TooMany.into());
+        }
+        let expires_at = now.saturating_add(self.ttl_secs);
+        self.entries.insert(nonce, NonceEntry { connection, expires_at, used: false });
+        *outstanding += 1;
+        Ok(nonce)
+    }
+
+    /// Redeems `nonce` for a request arriving on `connection` at `now`.
+    pub fn consume(&self, nonce: &[u8; NONCE_LEN], connection: &[u8; 32], now: u64) -> Result<(), NonceError> {
+        let mut entry = self.entries.get_mut(nonce).ok_or(NonceError::Unknown)?;
+        if entry.connection != *connection {
+            return Err(NonceError::Unknown);
+        }
+        if entry.used {
+            return Err(NonceError::Replayed);
+        }
+        if now >= entry.expires_at {
+            return Err(NonceError::Expired);
+        }
+        entry.used = true;
+        drop(entry);
+        self.release(connection);
+        Ok(())
+    }
+
+    /// Drops expired nonces; replays of them are then reported as unknown.
+    pub fn purge_expired(&self, now: u64) {
+        let mut released = Vec::new();
+        self.entries.retain(|_, entry| {
+            let keep = entry.expires_at > now;
+            if !keep && !entry.used {
+                released.push(entry.connection);
+            }
+            keep
+        });
+        for connection in &released {
+            self.release(connection);
+        }
+    }
+
+    /// Frees one unredeemed slot of `connection`.
+    fn release(&self, connection: &[u8; 32]) {
+        self.outstanding.remove_if_mut(connection, |_, count| {
+            *count -= 1;
+            *count == 0
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONN: [u8; 32] = [1; 32];
+    const OTHER: [u8; 32] = [2; 32];
+
+    #[test]
+    fn nonce_is_single_use() {
+        let cache = NonceCache::new(60, 4);
+        let nonce = cache.issue(CONN, 1000).unwrap();
+        assert_eq!(cache.consume(&nonce, &CONN, 1010), Ok(()));
+        assert_eq!(cache.consume(&nonce, &CONN, 1011), Err(NonceError::Replayed));
+    }
+
+    #[test]
+    fn nonce_expires_after_ttl() {
+        let cache = NonceCache::new(60, 4);
+        let nonce = cache.issue(CONN, 1000).unwrap();
+        assert_eq!(cache.consume(&nonce, &CONN, 1060), Err(NonceError::Expired));
+        cache.purge_expired(1060);
+        assert_eq!(cache.consume(&nonce, &CONN, 1060), Err(NonceError::Unknown));
+    }
+
+    #[test]
+    fn nonce_is_bound_to_its_connection() {
+        let cache = NonceCache::new(60, 4);
+        let nonce = cache.issue(CONN, 1000).unwrap();
+        assert_eq!(cache.consume(&nonce, &OTHER, 1010), Err(NonceError::Unknown));
+        assert_eq!(cache.consume(&nonce, &CONN, 1010), Ok(()));
+        assert_eq!(cache.consume(&[0; NONCE_LEN], &CONN, 1010), Err(NonceError::Unknown));
+    }
+
+    #[test]
+    fn purge_keeps_used_nonces_until_they_expire() {
+        let cache = NonceCache::new(60, 4);
+        let nonce = cache.issue(CONN, 1000).unwrap();
+        cache.consume(&nonce, &CONN, 1010).unwrap();
+        cache.purge_expired(1030);
+        assert_eq!(cache.consume(&nonce, &CONN, 1030), Err(NonceError::Replayed));
+    }
+
+    #[test]
+    fn issue_is_capped_per_connection() {
+        let cache = NonceCache::new(60, 2);
+        let first = cache.issue(CONN, 1000).unwrap();
+        cache.issue(CONN, 1000).unwrap();
+        let err = cache.issue(CONN, 1000).unwrap_err();
+        assert_eq!(err.downcast_ref::<NonceError>(), Some(&NonceError::TooMany));
+        // Other connections have their own allowance.
+        cache.issue(OTHER, 1000).unwrap();
+
+        // Redeeming frees a slot; a failed redemption does not.
+        assert!(cache.consume(&first, &OTHER, 1010).is_err());
+        assert!(cache.issue(CONN, 1010).is_err());
+        cache.consume(&first, &CONN, 1010).unwrap();
+        cache.issue(CONN, 1010).unwrap();
+        assert!(cache.issue(CONN, 1010).is_err());
+    }
+
+    #[test]
+    fn purge_frees_slots_of_expired_nonces() {
+        let cache = NonceCache::new(60, 1);
+        cache.issue(CONN, 1000).unwrap();
+        assert!(cache.issue(CONN, 1059).is_err());
+        cache.purge_expired(1060);
+        cache.issue(CONN, 1060).unwrap();
+        assert!(cache.outstanding.get(&OTHER).is_none());
+        assert_eq!(*cache.outstanding.get(&CONN).unwrap(), 1);
+    }
+}
@@ -0,0 +1,145 @@
+// src/infrastructure/pdp_adapter.rs
+//! Client of the Policy Decision Point.
+//!
+//! Access decisions are requested in the shape of an OpenID AuthZEN evaluation: the
+//! acting agent as `subject`, the `action` and `resource`, and the request's
+//! attributes together with the active policy version as `context`. The PDP answers
+//! with a boolean `decision` and may add a `reason` and `obligations` to the response
+//! `context`.
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::net::IpAddr;
+use std::time::Duration;
+use crate::config::PdpConfig;
+use crate::domain::context::{DevicePosture, RiskLevel};
+
+/// The agent an access decision is requested for, as established by its token.
+#[derive(Debug, Clone, Serialize)]
+pub struct Subject<'a> {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    /// The agent's ID.
+    pub id: &'a str,
+    pub properties: SubjectProperties<'a>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubjectProperties<'a> {
+    /// The user the agent acts for, if the identity provider named one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<&'a str>,
+    pub provider: &'a str,
+    pub scopes: Vec<&'a str>,
+    /// RTA session ID (hex).
+    pub session_id: String,
+    pub delegation_depth: u8,
+    pub risk_level: RiskLevel,
+    pub device_posture: DevicePosture,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<IpAddr>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Named<'a> {
+    name: &'a str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Resource<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    id: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct Evaluation<'a> {
+    subject: &'a Subject<'a>,
+    action: Named<'a>,
+    resource: Resource<'a>,
+    context: Map<String, Value>,
+}
+
+/// A duty the enforcing agent must fulfil along with the decision, e.g. audit logging.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Obligation {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub attributes: Map<String, Value>,
+}
+
+/// The PDP's answer to an evaluation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PolicyDecision {
+    pub permit: bool,
+    pub reason: Option<String>,
+    pub obligations: Vec<Obligation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EvaluationResponse {
+    decision: bool,
+    #[serde(default)]
+    context: ResponseContext,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ResponseContext {
+    reason: Option<String>,
+    #[serde(default)]
+    obligations: Vec<Obligation>,
+}
+
+/// HTTP client of the PDP; connections are pooled across evaluations, which happen on
+/// every authorize request.
+#[derive(Debug, Clone)]
+pub struct PdpClient {
+    http: Client,
+    config: PdpConfig,
+}
+
+impl PdpClient {
+    pub fn new(config: &PdpConfig) -> Result<Self> {
+        let http = Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()?;
+        Ok(Self { http, config: config.clone() })
+    }
+
+    /// Asks the PDP whether `subject` may perform `action` on `resource`.
+    ///
+    /// `attributes` are passed through in the evaluation context, next to the
+    /// `policy_version` the server enforces. Errors, including a PDP that cannot be
+    /// reached in time, leave the decision to the caller.
+    pub async fn evaluate(
+        &self,
+        subject: &Subject<'_>,
+        action: &str,
+        resource: &str,
+        attributes: &Map<String, Value>,
+        policy_version: u32,
+    ) -> Result<PolicyDecision> {
+        let mut context = attributes.clone();
+        context.insert("policy_version".to_string(), policy_version.into());
+        let evaluation = Evaluation {
+            subject,
+            action: Named { name: action },
+            resource: Resource { kind: "resource", id: resource },
+            context,
+        };
+        let resp = self.http.post(&self.config.endpoint)
+            .json(&evaluation)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("PDP answered with status {}", resp.status()));
+        }
+        let resp: EvaluationResponse = resp.json().await?;
+        Ok(PolicyDecision {
+            permit: resp.decision,
+            reason: resp.context.reason,
+            obligations: resp.context.obligations,
+        })
+    }
+}
@@ -0,0 +1,455 @@
+// src/infrastructure/push.rs
+//! Server-initiated pushes to connected agents.
+//!
+//! Every RTA session held on a connection gets a unidirectional control stream
+//! from the server, opened with the session's first push. Pushes are driven by the
+//! [`DomainEvent`]s published to [`AppState::events`]: a session hears about its own
+//! revocation, context changes, refreshes and step-up requests, and every live session
+//! hears about policy updates.
+//!
+//! Each [`PushMessage`] carries a per-session sequence number starting at 1. The agent
+//! acknowledges them cumulatively with a [`PushAck`] on a unidirectional stream of its
+//! own. Pushes still unacknowledged after [`ACK_TIMEOUT`] are sent again, in order, on
+//! a new control stream that replaces the old one, so agents must skip sequence
+//! numbers they have already applied. `Revoked` is the last push of a session; its
+//! control stream is finished once it has been acknowledged. A connection that falls
+//! behind the event stream pushes [`Push::Resync`] to each of its live sessions.
+use quinn::{Connection, SendStream, VarInt};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinSet;
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::warn;
+use crate::application::state::AppState;
+use crate::domain::events::DomainEvent;
+use crate::infrastructure::event_broker::parse_session_id;
+use crate::infrastructure::framing::{FrameCodec, Message, MessageType};
+use crate::infrastructure::quic_session::ConnectionSession;
+
+/// How long a push may go unacknowledged, or a control stream stay blocked, before
+/// the pending pushes are sent again on a new control stream.
+pub const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Acknowledgements queued between the client's streams and the push task.
+pub const ACK_QUEUE_LEN: usize = 64;
+
+/// Unacknowledged pushes kept per session; beyond this the oldest are dropped.
+const MAX_UNACKED: usize = 256;
+
+/// Error code resetting a control stream that was replaced by a new one.
+const STREAM_REPLACED: VarInt = VarInt::from_u32(0);
+
+/// What the server tells an agent about one of its sessions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Push {
+    /// The session was revoked; its tokens are no longer accepted.
+    Revoked,
+    /// The session's context changed; its token no longer validates until refreshed.
+    ContextChanged,
+    /// A token of `generation` was issued for the session; earlier ones are invalid.
+    TokenRefreshed { generation: u32 },
+    /// The user must authenticate again; the agent needs a fresh token exchange.
+    StepUpRequired {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// A new policy set is active; a refresh binds the session to it.
+    PolicyUpdated { policy_version: u32 },
+    /// Pushes for the session may have been lost; the agent must re-validate its
+    /// token, for example by refreshing it.
+    Resync,
+}
+
+/// A push on a session's control stream.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushMessage {
+    /// RTA session ID (hex).
+    pub session_id: String,
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub push: Push,
+}
+
+/// Acknowledges every push of the session up to and including `sequence`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushAck {
+    /// RTA session ID (hex).
+    pub session_id: String,
+    pub sequence: u64,
+}
+
+impl Message for PushMessage {
+    const TYPE: MessageType = MessageType::Push;
+}
+
+impl Message for PushAck {
+    const TYPE: MessageType = MessageType::PushAck;
+}
+
+/// Sessions a push is addressed to.
+enum Recipients {
+    Session([u8; 16]),
+    AllLive,
+}
+
+impl Push {
+    /// The push an event calls for, if any, and who it goes to.
+    fn for_event(event: &DomainEvent) -> Option<(Recipients, Push)> {
+        let (session_id, push) = match event {
+            DomainEvent::TokenRevoked { session_id } => (session_id, Push::Revoked),
+            DomainEvent::ContextChanged { session_id } => (session_id, Push::ContextChanged),
+            DomainEvent::TokenRefreshed { session_id, generation } => {
+                (session_id, Push::TokenRefreshed { generation: *generation })
+            }
+            DomainEvent::StepUpRequired { session_id, reason } => {
+                (session_id, Push::StepUpRequired { reason: reason.clone() })
+            }
+            DomainEvent::PolicyUpdated { policy_version } => {
+                let push = Push::PolicyUpdated { policy_version: *policy_version };
+                return Some((Recipients::AllLive, push));
+            }
+            _ => return None,
+        };
+        Some((Recipients::Session(parse_session_id(session_id).ok()?), push))
+    }
+}
+
+impl Recipients {
+    /// The addressed sessions that are held on `session`'s connection.
+    fn on(&self, session: &ConnectionSession, state: &AppState) -> Vec<[u8; 16]> {
+        match self {
+            Recipients::Session(id) if session.owns(id) => vec![*id],
+            Recipients::Session(_) => Vec::new(),
+            Recipients::AllLive => session.sessions()
+                .into_iter()
+                .filter(|id| state.sessions.get(id).is_some_and(|s| !s.revoked))
+                .collect(),
+        }
+    }
+}
+
+/// A push awaiting acknowledgement.
+struct Pending {
+    message: PushMessage,
+    sent_at: Instant,
+}
+
+/// Sequencing and acknowledgement state of one session's pushes.
+struct Outbox {
+    session_id: [u8; 16],
+    next_sequence: u64,
+    unacked: VecDeque<Pending>,
+    /// Set once `Revoked` was pushed; nothing follows it.
+    closed: bool,
+}
+
+impl Outbox {
+    fn new(session_id: [u8; 16]) -> Self {
+        Self { session_id, next_sequence: 1, unacked: VecDeque::new(), closed: false }
+    }
+
+    /// Queues `push` as sent at `now`; the message to write, unless the session was
+    /// already revoked.
+    fn push(&mut self, push: Push, now: Instant) -> Option<PushMessage> {
+        if self.closed {
+            return None;
+        }
+        self.closed = push == Push::Revoked;
+        let message = PushMessage {
+            session_id: hex::encode(self.session_id),
+            sequence: self.next_sequence,
+            push,
+        };
+        self.next_sequence += 1;
+        if self.unacked.len() == MAX_UNACKED {
+            let dropped = self.unacked.pop_front().map(|p| p.message.sequence);
+            warn!("Dropping unacknowledged push {:?} of session {}", dropped, message.session_id);
+        }
+        self.unacked.push_back(Pending { message: message.clone(), sent_at: now });
+        Some(message)
+    }
+
+    fn ack(&mut self, sequence: u64) {
+        while self.unacked.front().is_some_and(|p| p.message.sequence <= sequence) {
+            self.unacked.pop_front();
+        }
+    }
+
+    /// Every unacknowledged push, in order, if the oldest has waited longer than
+    /// [`ACK_TIMEOUT`] at `now`; they count as sent again at `now`.
+    fn take_overdue(&mut self, now: Instant) -> Vec<PushMessage> {
+        let overdue = self.unacked.front().is_some_and(|p| now.duration_since(p.sent_at) >= ACK_TIMEOUT);
+        if !overdue {
+            return Vec::new();
+        }
+        self.unacked.iter_mut()
+            .map(|pending| {
+                pending.sent_at = now;
+                pending.message.clone()
+            })
+            .collect()
+    }
+
+    /// Whether the session was revoked and the agent has acknowledged it.
+    fn done(&self) -> bool {
+        self.closed && self.unacked.is_empty()
+    }
+}
+
+/// A session's control stream, opened with the first write after it was replaced.
+#[derive(Default)]
+struct ControlStream {
+    stream: Option<SendStream>,
+}
+
+impl ControlStream {
+    /// Writes a push, opening the stream if there is none. A stream that fails or
+    /// stays blocked is dropped; the push is resent on its successor.
+    async fn write(&mut self, conn: &Connection, message: &PushMessage) {
+        let written = tokio::time::timeout(ACK_TIMEOUT, async {
+            if self.stream.is_none() {
+                self.stream = Some(conn.open_uni().await?);
+            }
+            let stream = self.stream.as_mut().expect("control stream just opened");
+            FrameCodec::default().write_message(stream, message).await?;
+            anyhow::Ok(())
+        })
+        .await;
+        let failure = match written {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => e,
+            Err(elapsed) => elapsed.into(),
+        };
+        warn!("Failed to push to session {}: {:?}", message.session_id, failure);
+        self.replace();
+    }
+
+    /// Resets the stream so that the next write opens a new one.
+    fn replace(&mut self) {
+        if let Some(mut stream) = self.stream.take() {
+            let _ = stream.reset(STREAM_REPLACED);
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(mut stream) = self.stream.take() {
+            let _ = stream.finish();
+        }
+    }
+}
+
+/// What the push channel hands a session's outbox task.
+enum OutboxCommand {
+    Push(Push),
+    Ack(u64),
+}
+
+/// Delivers one session's pushes and retransmits them until they are acknowledged.
+///
+/// Each session has its own task, so a blocked control stream delays only the
+/// pushes of its own session. The task ends once the session's revocation is
+/// acknowledged or the push channel drops its queue.
+async fn run_outbox(conn: Connection, session_id: [u8; 16], mut commands: mpsc::UnboundedReceiver<OutboxCommand>) {
+    let mut outbox = Outbox::new(session_id);
+    let mut control = ControlStream::default();
+    let mut resend = tokio::time::interval(ACK_TIMEOUT / 2);
+    resend.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(OutboxCommand::Push(push)) => {
+                    if let Some(message) = outbox.push(push, Instant::now()) {
+                        control.write(&conn, &message).await;
+                    }
+                }
+                Some(OutboxCommand::Ack(sequence)) => {
+                    outbox.ack(sequence);
+                    if outbox.done() {
+                        control.finish();
+                        return;
+                    }
+                }
+                None => return,
+            },
+            now = resend.tick() => {
+                let overdue = outbox.take_overdue(now);
+                if !overdue.is_empty() {
+                    control.replace();
+                    for message in &overdue {
+                        control.write(&conn, message).await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Queues of the outbox tasks of a connection's sessions.
+struct Outboxes {
+    conn: Connection,
+    queues: HashMap<[u8; 16], mpsc::UnboundedSender<OutboxCommand>>,
+    tasks: JoinSet<()>,
+}
+
+impl Outboxes {
+    fn new(conn: Connection) -> Self {
+        Self { conn, queues: HashMap::new(), tasks: JoinSet::new() }
+    }
+
+    /// Hands `push` to the session's outbox, starting its task on the first push.
+    fn push(&mut self, session_id: [u8; 16], push: Push) {
+        let queue = self.queues.entry(session_id).or_insert_with(|| {
+            let (queue, commands) = mpsc::unbounded_channel();
+            self.tasks.spawn(run_outbox(self.conn.clone(), session_id, commands));
+            queue
+        });
+        let _ = queue.send(OutboxCommand::Push(push));
+    }
+
+    fn ack(&mut self, session_id: [u8; 16], sequence: u64) {
+        if let Some(queue) = self.queues.get(&session_id) {
+            let _ = queue.send(OutboxCommand::Ack(sequence));
+        }
+    }
+
+    /// Forgets the queues of outbox tasks that have ended.
+    fn prune(&mut self) {
+        self.queues.retain(|_, queue| !queue.is_closed());
+    }
+}
+
+/// Delivers pushes to the sessions held on a connection until it closes.
+///
+/// `acks` carries the [`PushAck`]s read from the client's unidirectional streams.
+/// If events were missed because the channel fell behind, every session on the
+/// connection is told to re-validate: live ones with [`Push::Resync`], revoked or
+/// purged ones with [`Push::Revoked`].
+pub async fn run_push_channel(
+    session: Arc<ConnectionSession>,
+    state: Arc<AppState>,
+    mut events: broadcast::Receiver<DomainEvent>,
+    mut acks: mpsc::Receiver<PushAck>,
+) {
+    let conn = session.connection().clone();
+    let mut outboxes = Outboxes::new(conn.clone());
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    let Some((recipients, push)) = Push::for_event(&event) else {
+                        continue;
+                    };
+                    for id in recipients.on(&session, &state) {
+                        outboxes.push(id, push.clone());
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Push channel to {} missed {} event(s); resyncing its sessions", session.client_ip(), missed);
+                    for id in session.sessions() {
+                        let live = state.sessions.get(&id).is_some_and(|s| !s.revoked);
+                        outboxes.push(id, if live { Push::Resync } else { Push::Revoked });
+                    }
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            Some(ack) = acks.recv() => {
+                if let Ok(id) = parse_session_id(&ack.session_id) {
+                    outboxes.ack(id, ack.sequence);
+                }
+            }
+            Some(_) = outboxes.tasks.join_next() => outboxes.prune(),
+            _ = conn.closed() => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION: [u8; 16] = [7; 16];
+
+    fn sequences(messages: &[PushMessage]) -> Vec<u64> {
+        messages.iter().map(|m| m.sequence).collect()
+    }
+
+    #[test]
+    fn pushes_are_numbered_from_one() {
+        let mut outbox = Outbox::new(SESSION);
+        let now = Instant::now();
+        let first = outbox.push(Push::ContextChanged, now).unwrap();
+        let second = outbox.push(Push::TokenRefreshed { generation: 2 }, now).unwrap();
+        assert_eq!((first.sequence, second.sequence), (1, 2));
+        assert_eq!(first.session_id, hex::encode(SESSION));
+    }
+
+    #[test]
+    fn ack_is_cumulative() {
+        let mut outbox = Outbox::new(SESSION);
+        let now = Instant::now();
+        for _ in 0..3 {
+            outbox.push(Push::ContextChanged, now);
+        }
+        outbox.ack(2);
+        let overdue = outbox.take_overdue(now + ACK_TIMEOUT);
+        assert_eq!(sequences(&overdue), [3]);
+        // A stale acknowledgement changes nothing.
+        outbox.ack(1);
+        assert_eq!(sequences(&outbox.take_overdue(now + 2 * ACK_TIMEOUT)), [3]);
+    }
+
+    #[test]
+    fn resends_all_unacked_once_the_oldest_is_overdue() {
+        let mut outbox = Outbox::new(SESSION);
+        let start = Instant::now();
+        outbox.push(Push::ContextChanged, start);
+        outbox.push(Push::ContextChanged, start + ACK_TIMEOUT / 2);
+        assert!(outbox.take_overdue(start + ACK_TIMEOUT - Duration::from_millis(1)).is_empty());
+
+        let resent_at = start + ACK_TIMEOUT;
+        assert_eq!(sequences(&outbox.take_overdue(resent_at)), [1, 2]);
+        // Resent pushes wait a full timeout again.
+        assert!(outbox.take_overdue(resent_at + ACK_TIMEOUT / 2).is_empty());
+        assert_eq!(sequences(&outbox.take_overdue(resent_at + ACK_TIMEOUT)), [1, 2]);
+    }
+
+    #[test]
+    fn nothing_follows_revocation() {
+        let mut outbox = Outbox::new(SESSION);
+        let now = Instant::now();
+        outbox.push(Push::ContextChanged, now);
+        let revoked = outbox.push(Push::Revoked, now).unwrap();
+        assert!(outbox.push(Push::ContextChanged, now).is_none());
+        assert!(!outbox.done());
+        outbox.ack(1);
+        assert!(!outbox.done());
+        outbox.ack(revoked.sequence);
+        assert!(outbox.done());
+    }
+
+    #[test]
+    fn drops_oldest_beyond_the_unacked_limit() {
+        let mut outbox = Outbox::new(SESSION);
+        let now = Instant::now();
+        for _ in 0..MAX_UNACKED + 2 {
+            outbox.push(Push::ContextChanged, now);
+        }
+        let overdue = outbox.take_overdue(now + ACK_TIMEOUT);
+        assert_eq!(overdue.len(), MAX_UNACKED);
+        assert_eq!(overdue[0].sequence, 3);
+        assert_eq!(overdue.last().unwrap().sequence, MAX_UNACKED as u64 + 2);
+    }
+
+    #[test]
+    fn resync_serializes_with_its_type() {
+        let message = PushMessage { session_id: "00".into(), sequence: 4, push: Push::Resync };
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!({ "session_id": "00", "sequence": 4, "type": "resync" }),
+        );
+    }
+}
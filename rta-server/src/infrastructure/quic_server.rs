@@ -1,46 +1,527 @@
 // src/infrastructure/quic_server.rs
 use anyhow::{anyhow, Result};
-use quinn::{Endpoint, Connection};
+use quinn::{Endpoint, Connection, RecvStream, SendStream, VarInt, ZeroRttAccepted};
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use rustls_pemfile::{certs, private_key};
 use serde::{Deserialize, Serialize};
-use std::{fs, net::SocketAddr, sync::Arc};
+use serde_json::{Map, Value};
+use std::{collections::BTreeSet, fs, net::SocketAddr, sync::Arc, time::Duration};
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
 use tracing::{info, error};
 use base64::engine::general_purpose::STANDARD; // using new base64 encode engine
 use base64::Engine;
-use hex;
 
-use crate::config::{ServerConfig, IdpConfig, TokenConfig, RedisConfig, IdpProviders};
-use crate::application::commands::{IssueTokenCommand, handle_issue_token};
-use crate::domain::token::RTAToken;
+use crate::config::ServerConfig;
+use crate::application::commands::{
+    DelegateTokenCommand, IssueTokenCommand, MintedJwt, OAuthTokenRejected, RefreshTokenCommand, RevokeTokenCommand,
+    TranslateTokenCommand, handle_delegate_token, handle_issue_token, handle_refresh_token, handle_revoke_token,
+    handle_translate_token, mint_jwt,
+};
+use crate::application::queries::{
+    authenticate_client, authorize_action, introspect_token, validate_revocation, validate_token_holder,
+    ClientAuthError, TokenIntrospection,
+};
+use crate::application::state::AppState;
+use crate::domain::clock::unix_now;
+use rta_verify::caveat::AccessRequest;
+use crate::domain::binding::BINDING_LEN;
+use crate::domain::context::{AuthContext, DevicePosture, ScopeNotGranted};
+use crate::domain::delegation::DelegationError;
+use crate::domain::token::{self, RTAToken, TokenError};
+use crate::infrastructure::client_auth::{client_verifier, AgentMapping, AgentMismatch};
+use crate::infrastructure::framing::{Frame, FrameCodec, FrameError, Message, MessageType};
+use crate::infrastructure::h3_server::{handle_h3_connection, ALPN_H3};
+use crate::infrastructure::metrics::{CountingServerConfig, CountingSessionStore};
+use crate::infrastructure::nonce_cache::{NonceError, NONCE_LEN};
+use crate::infrastructure::pdp_adapter::{Obligation, PolicyDecision};
+use crate::infrastructure::push::{run_push_channel, PushAck, ACK_QUEUE_LEN};
+use crate::infrastructure::quic_session::ConnectionSession;
+use crate::infrastructure::standard_exchange::{self, GRANT_TYPE_TOKEN_EXCHANGE};
+
+/// Exchanges an OAuth token for an RTAToken.
+pub const GRANT_TYPE_EXCHANGE: &str = "urn:ietf:params:oauth:grant-type:rta_token_exchange";
+/// Obtains a delegated RTAToken on behalf of the holder of a parent RTAToken.
+pub const GRANT_TYPE_DELEGATION: &str = "urn:ietf:params:oauth:grant-type:rta_delegation";
+/// Mints a JWT for a named audience from a presented RTAToken.
+pub const GRANT_TYPE_JWT_TRANSLATION: &str = "urn:ietf:params:oauth:grant-type:rta_jwt_translation";
+
+/// ALPN protocol of the framed RTA protocol. QUIC clients must negotiate a protocol
+/// (RFC 9001 §8.1); this one or HTTP/3.
+pub const ALPN_RTA: &[u8] = b"rta/1";
+
+/// QUIC application error codes a failed request ends its stream with.
+///
+/// Token validation failures each get a distinct code so clients can tell, for example,
+/// an expired token (re-exchange) from a revoked one (stop). Each code also maps to
+/// the RFC 6749 §5.2 error reported in the [`ErrorResponse`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ExchangeErrorCode {
+    Internal = 0x01,
+    BadSignature = 0x10,
+    ContextMismatch = 0x11,
+    Expired = 0x12,
+    NotYetValid = 0x13,
+    UnknownKey = 0x14,
+    MalformedToken = 0x15,
+    Revoked = 0x16,
+    StaleGeneration = 0x17,
+    BindingMismatch = 0x18,
+    DelegationDenied = 0x19,
+    InvalidCaveatChain = 0x1a,
+    CaveatNotSatisfied = 0x1b,
+    AudienceMismatch = 0x1c,
+    ClientAuthFailed = 0x1d,
+    /// The nonce is missing, unknown, was issued to another connection or was used before.
+    NonceReplayed = 0x1e,
+    NonceExpired = 0x1f,
+    /// A frame on the stream was truncated, oversized, of an unknown or unexpected type,
+    /// or its payload did not parse.
+    MalformedFrame = 0x20,
+    /// A required request member is missing or invalid.
+    InvalidRequest = 0x21,
+    UnsupportedGrantType = 0x22,
+    /// The identity provider rejected the OAuth token.
+    InvalidGrant = 0x23,
+    /// The requested agent, or the holder of a delegated parent token, is not the one
+    /// authenticated by the client certificate.
+    AgentMismatch = 0x24,
+    /// The connection holds too many unredeemed challenge nonces.
+    TooManyChallenges = 0x25,
+    /// A requested scope is not covered by the presented grant.
+    ScopeNotGranted = 0x26,
+}
+
+impl From<&TokenError> for ExchangeErrorCode {
+    fn from(err: &TokenError) -> Self {
+        match err {
+            TokenError::BadSignature => ExchangeErrorCode::BadSignature,
+            TokenError::ContextMismatch => ExchangeErrorCode::ContextMismatch,
+            TokenError::Expired => ExchangeErrorCode::Expired,
+            TokenError::NotYetValid => ExchangeErrorCode::NotYetValid,
+            TokenError::UnknownKey(_) => ExchangeErrorCode::UnknownKey,
+            TokenError::MalformedHeader(_) | TokenError::BadEncoding => ExchangeErrorCode::MalformedToken,
+            TokenError::Revoked => ExchangeErrorCode::Revoked,
+            TokenError::StaleGeneration => ExchangeErrorCode::StaleGeneration,
+            TokenError::BindingMismatch => ExchangeErrorCode::BindingMismatch,
+            TokenError::InvalidCaveatChain => ExchangeErrorCode::InvalidCaveatChain,
+            TokenError::CaveatNotSatisfied(_) => ExchangeErrorCode::CaveatNotSatisfied,
+            TokenError::AudienceMismatch => ExchangeErrorCode::AudienceMismatch,
+        }
+    }
+}
+
+impl ExchangeErrorCode {
+    /// The code for an exchange failure; errors without a dedicated code are internal.
+    pub fn for_error(err: &anyhow::Error) -> Self {
+        if let Some(err) = err.downcast_ref::<TokenError>() {
+            return err.into();
+        }
+        if err.downcast_ref::<DelegationError>().is_some() {
+            return ExchangeErrorCode::DelegationDenied;
+        }
+        if err.downcast_ref::<ClientAuthError>().is_some() {
+            return ExchangeErrorCode::ClientAuthFailed;
+        }
+        if err.downcast_ref::<AgentMismatch>().is_some() {
+            return ExchangeErrorCode::AgentMismatch;
+        }
+        if err.downcast_ref::<FrameError>().is_some() {
+            return ExchangeErrorCode::MalformedFrame;
+        }
+        if let Some(err) = err.downcast_ref::<RequestError>() {
+            return match err {
+                RequestError::Invalid(_) => ExchangeErrorCode::InvalidRequest,
+                RequestError::UnsupportedGrantType(_) => ExchangeErrorCode::UnsupportedGrantType,
+            };
+        }
+        if err.downcast_ref::<ScopeNotGranted>().is_some() {
+            return ExchangeErrorCode::ScopeNotGranted;
+        }
+        if err.downcast_ref::<OAuthTokenRejected>().is_some() {
+            return ExchangeErrorCode::InvalidGrant;
+        }
+        if let Some(err) = err.downcast_ref::<NonceError>() {
+            return match err {
+                NonceError::Expired => ExchangeErrorCode::NonceExpired,
+                NonceError::Unknown | NonceError::Replayed => ExchangeErrorCode::NonceReplayed,
+                NonceError::TooMany => ExchangeErrorCode::TooManyChallenges,
+            };
+        }
+        ExchangeErrorCode::Internal
+    }
+
+    /// The RFC 6749 §5.2 `error` value reported with this code.
+    pub fn oauth_error(self) -> &'static str {
+        match self {
+            ExchangeErrorCode::Internal => "server_error",
+            ExchangeErrorCode::MalformedFrame
+            | ExchangeErrorCode::InvalidRequest
+            | ExchangeErrorCode::NonceReplayed
+            | ExchangeErrorCode::NonceExpired
+            | ExchangeErrorCode::TooManyChallenges => "invalid_request",
+            ExchangeErrorCode::UnsupportedGrantType => "unsupported_grant_type",
+            ExchangeErrorCode::ClientAuthFailed | ExchangeErrorCode::AgentMismatch => "invalid_client",
+            // RFC 8693 §2.2.2: the requested audience is not acceptable.
+            ExchangeErrorCode::AudienceMismatch => "invalid_target",
+            ExchangeErrorCode::ScopeNotGranted => "invalid_scope",
+            ExchangeErrorCode::BadSignature
+            | ExchangeErrorCode::ContextMismatch
+            | ExchangeErrorCode::Expired
+            | ExchangeErrorCode::NotYetValid
+            | ExchangeErrorCode::UnknownKey
+            | ExchangeErrorCode::MalformedToken
+            | ExchangeErrorCode::Revoked
+            | ExchangeErrorCode::StaleGeneration
+            | ExchangeErrorCode::BindingMismatch
+            | ExchangeErrorCode::DelegationDenied
+            | ExchangeErrorCode::InvalidCaveatChain
+            | ExchangeErrorCode::CaveatNotSatisfied
+            | ExchangeErrorCode::InvalidGrant => "invalid_grant",
+        }
+    }
+}
+
+/// A request that is well-framed but cannot be served as sent.
+#[derive(Debug, Error)]
+pub enum RequestError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("unsupported grant type {0:?}")]
+    UnsupportedGrantType(String),
+}
+
+/// RFC 6749 §5.2 error response, sent in an error frame when a request fails.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_uri: Option<String>,
+}
+
+impl ErrorResponse {
+    /// Describes `err`, which failed a request with `code`. Internal errors are not
+    /// described to the client.
+    pub fn for_error(code: ExchangeErrorCode, err: &anyhow::Error) -> Self {
+        let error_description = match code {
+            ExchangeErrorCode::Internal => "internal server error".to_string(),
+            _ => err.to_string(),
+        };
+        Self { error: code.oauth_error().to_string(), error_description: Some(error_description), error_uri: None }
+    }
+}
+
+impl From<ExchangeErrorCode> for VarInt {
+    fn from(code: ExchangeErrorCode) -> Self {
+        VarInt::from_u32(code as u32)
+    }
+}
 
 /// Structure representing the token exchange request.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TokenExchangeRequest {
     pub grant_type: String,
+    /// Base64 nonce from a challenge issued on the same connection; single-use.
+    pub nonce: Option<String>,
+    /// OAuth token to exchange; unused for delegation.
+    #[serde(default)]
     pub oauth_token: String,
+    /// Agent the token is issued to; optional for RFC 8693 requests with an `actor_token`.
+    #[serde(default)]
     pub agent_id: String,
     // Added field for the IdP provider selection (e.g., "azure", "okta", "auth0").
     pub provider: Option<String>,
+    /// Device posture reported by the agent; becomes part of the token context.
+    #[serde(default)]
+    pub device_posture: DevicePosture,
+    /// Service the issued token is intended for.
+    pub audience: Option<String>,
+    /// Delegation only: the delegating holder's Base64-encoded RTAToken.
+    pub parent_token: Option<String>,
+    /// JWT translation only: the Base64-encoded RTAToken to translate.
+    pub rtatoken: Option<String>,
+    /// Delegation and JWT translation: Base64 proof of the presented token's binding
+    /// when it was bound to another connection (see `domain::binding::binding_proof`).
+    pub binding_proof: Option<String>,
+    /// Delegation only: scopes for the child token; defaults to the parent's. Not
+    /// accepted in form-encoded requests, which use `scope`.
+    #[serde(default)]
+    pub scopes: Option<Vec<String>>,
+    /// Space-delimited alternative to `scopes`, as in OAuth.
+    pub scope: Option<String>,
+    /// Delegation only: how many further levels the child may delegate.
+    #[serde(default)]
+    pub max_depth: u8,
+    /// Issue a token the holder can narrow with caveats before passing it on.
+    #[serde(default)]
+    pub attenuable: bool,
+    /// Also return a short-lived JWT mirror of the issued token.
+    #[serde(default)]
+    pub jwt: bool,
+    /// RFC 8693 only: the token of the party on whose behalf the token is requested.
+    pub subject_token: Option<String>,
+    pub subject_token_type: Option<String>,
+    /// RFC 8693 only: an RTAToken of the acting agent, naming the agent in place of
+    /// `agent_id`.
+    pub actor_token: Option<String>,
+    pub actor_token_type: Option<String>,
+    /// RFC 8693 only: type of token to issue; an RTAToken by default.
+    pub requested_token_type: Option<String>,
+}
+
+/// Introspection request sent over the exchange stream by a resource server.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IntrospectionRequest {
+    /// Base64-encoded RTAToken to introspect.
+    pub token: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Access the resource server is about to grant, for evaluating caveats.
+    pub resource: Option<String>,
+    pub action: Option<String>,
+    pub client_ip: Option<std::net::IpAddr>,
+}
+
+/// Asks for a nonce to include in the next exchange request on this connection; the
+/// payload is an empty object.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChallengeRequest {}
+
+/// Server-issued challenge.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChallengeResponse {
+    /// Base64-encoded nonce.
+    pub nonce: String,
+    pub expires_in: u64,
+}
+
+/// Asks whether the holder of `rtatoken` may perform `action` on `resource` now.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthorizeRequest {
+    /// Base64-encoded RTAToken of the acting agent.
+    pub rtatoken: String,
+    pub resource: String,
+    pub action: String,
+    /// Further facts about the access for the PDP, e.g. the amount of a transfer.
+    #[serde(default)]
+    pub attributes: Map<String, Value>,
+    /// Base64 proof of the token's binding when it was bound to another connection.
+    pub binding_proof: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Permit,
+    Deny,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthorizeResponse {
+    pub decision: Decision,
+    /// Why the access was denied.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Duties the agent must fulfil along with the decision.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub obligations: Vec<Obligation>,
+}
+
+/// Asks for a new token for a session held on this connection, bound to the session's
+/// current context.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshRequest {
+    /// Base64-encoded latest token of the session; may have expired.
+    pub rtatoken: String,
+    /// Device posture to bind the new token to; defaults to the last reported one.
+    pub device_posture: Option<DevicePosture>,
+    /// Base64 proof of the token's binding when it was bound to another connection.
+    pub binding_proof: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshResponse {
+    /// Base64-encoded token; supersedes every earlier token of the session.
+    pub rtatoken: String,
+    pub generation: u32,
+    pub expires_in: u64,
+}
+
+/// Revokes the session of a presented token, together with every session delegated
+/// from it, in the manner of RFC 7009. Revoking a session that is already revoked
+/// succeeds.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevocationRequest {
+    /// Base64-encoded RTAToken of the session; may have expired.
+    pub token: String,
+    /// Base64 proof of the token's binding when it was bound to another connection.
+    pub binding_proof: Option<String>,
+}
+
+/// Confirms a revocation; the payload is an empty object.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RevocationResponse {}
+
+/// The agent's device posture, sent on a unidirectional stream whenever it changes.
+/// Later refreshes on the connection bind to it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PostureReport {
+    pub device_posture: DevicePosture,
+}
+
+/// Requests accepted on bidirectional streams, told apart by their frame type.
+#[derive(Debug)]
+pub enum StreamRequest {
+    Challenge(ChallengeRequest),
+    Introspection(IntrospectionRequest),
+    Exchange(Box<TokenExchangeRequest>),
+    Authorize(AuthorizeRequest),
+    Refresh(RefreshRequest),
+    Revocation(RevocationRequest),
+}
+
+impl StreamRequest {
+    /// Decodes a request frame; response and one-way frames are rejected.
+    pub fn from_frame(frame: &Frame) -> Result<Self, FrameError> {
+        Ok(match frame.kind {
+            MessageType::ChallengeRequest => StreamRequest::Challenge(frame.decode()?),
+            MessageType::IntrospectionRequest => StreamRequest::Introspection(frame.decode()?),
+            MessageType::ExchangeRequest => StreamRequest::Exchange(Box::new(frame.decode()?)),
+            MessageType::AuthorizeRequest => StreamRequest::Authorize(frame.decode()?),
+            MessageType::RefreshRequest => StreamRequest::Refresh(frame.decode()?),
+            MessageType::RevocationRequest => StreamRequest::Revocation(frame.decode()?),
+            other => return Err(FrameError::NotAccepted(other)),
+        })
+    }
+
+    /// Whether the request may be answered from 0-RTT early data, which an attacker
+    /// can replay. Introspection and authorization only read state. Exchanges,
+    /// refreshes and revocations change it, and challenges are bound to the completed
+    /// handshake, so these wait until the handshake is confirmed.
+    pub fn replay_safe(&self) -> bool {
+        matches!(self, StreamRequest::Introspection(_) | StreamRequest::Authorize(_))
+    }
+}
+
+/// Messages accepted on unidirectional streams from the client.
+#[derive(Debug)]
+pub enum OneWayMessage {
+    PostureReport(PostureReport),
+    PushAck(PushAck),
+}
+
+impl OneWayMessage {
+    /// Decodes a one-way frame; requests and responses are rejected.
+    pub fn from_frame(frame: &Frame) -> Result<Self, FrameError> {
+        Ok(match frame.kind {
+            MessageType::PostureReport => OneWayMessage::PostureReport(frame.decode()?),
+            MessageType::PushAck => OneWayMessage::PushAck(frame.decode()?),
+            other => return Err(FrameError::NotAccepted(other)),
+        })
+    }
 }
 
 /// Structure representing the token exchange response.
-#[derive(Debug, Serialize)]
+///
+/// RTA grants are answered with `rtatoken` and `jwt`, RFC 8693 token exchanges with
+/// the members of an RFC 8693 §2.2.1 response instead.
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct TokenExchangeResponse {
-    pub rtatoken: String, // Base64-encoded token
+    /// Base64-encoded token; absent for JWT translation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtatoken: Option<String>,
+    /// JWT mirror of the token, if requested, or the result of a JWT translation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt: Option<String>,
+    /// The issued token: a Base64-encoded RTAToken or a JWT, per `issued_token_type`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issued_token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_in: Option<u64>,
+    /// Space-delimited scopes of the issued token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+impl Message for ChallengeRequest {
+    const TYPE: MessageType = MessageType::ChallengeRequest;
+}
+
+impl Message for ChallengeResponse {
+    const TYPE: MessageType = MessageType::ChallengeResponse;
+}
+
+impl Message for TokenExchangeRequest {
+    const TYPE: MessageType = MessageType::ExchangeRequest;
+}
+
+impl Message for TokenExchangeResponse {
+    const TYPE: MessageType = MessageType::ExchangeResponse;
+}
+
+impl Message for IntrospectionRequest {
+    const TYPE: MessageType = MessageType::IntrospectionRequest;
+}
+
+impl Message for TokenIntrospection {
+    const TYPE: MessageType = MessageType::IntrospectionResponse;
+}
+
+impl Message for AuthorizeRequest {
+    const TYPE: MessageType = MessageType::AuthorizeRequest;
+}
+
+impl Message for AuthorizeResponse {
+    const TYPE: MessageType = MessageType::AuthorizeResponse;
+}
+
+impl Message for RefreshRequest {
+    const TYPE: MessageType = MessageType::RefreshRequest;
+}
+
+impl Message for RefreshResponse {
+    const TYPE: MessageType = MessageType::RefreshResponse;
+}
+
+impl Message for RevocationRequest {
+    const TYPE: MessageType = MessageType::RevocationRequest;
+}
+
+impl Message for RevocationResponse {
+    const TYPE: MessageType = MessageType::RevocationResponse;
+}
+
+impl Message for PostureReport {
+    const TYPE: MessageType = MessageType::PostureReport;
+}
+
+impl Message for ErrorResponse {
+    const TYPE: MessageType = MessageType::Error;
 }
 
 /// Runs the QUIC Token Exchange endpoint.
 ///
 /// Binds the QUIC endpoint to the supplied address, listens for incoming connections,
-/// and processes token exchange requests. The idp_providers reference is cloned into each async task
-/// to satisfy the `'static` requirement.
+/// and processes token exchange requests. Connections that negotiate HTTP/3 by ALPN
+/// are served the HTTP/3 token endpoint instead of the framed protocol. The shared application state is
+/// reference-counted into each async task to satisfy the `'static` requirement.
+///
+/// Clients can resume their TLS session with a single-use ticket from an earlier
+/// connection. With `early_data` enabled they may also send requests in 0-RTT early
+/// data on resumption; see [`handle_exchange_connection`] for which are answered
+/// before the handshake is confirmed.
 pub async fn run_quic_token_exchange(
     addr: SocketAddr,
     server_config: &ServerConfig,
-    idp_providers: &IdpProviders, // Updated parameter type
-    token_config: &TokenConfig,
-    _redis_config: &RedisConfig, // Not used for in-memory binding.
+    state: Arc<AppState>,
 ) -> Result<()> {
     // Load TLS certificates.
     let cert_file = fs::File::open(&server_config.cert_path)?;
@@ -54,91 +535,579 @@ pub async fn run_quic_token_exchange(
     let private_key: PrivateKeyDer<'static> = private_key(&mut key_reader)?
         .ok_or_else(|| anyhow!("No private key found"))?;
 
-    // Build the TLS configuration for QUIC.
-    let tls_config = rustls::ServerConfig::builder()
-        .with_no_client_auth()
-        .with_single_cert(certs, private_key)?;
+    // Build the TLS configuration for QUIC, verifying agent certificates if configured.
+    let tls_builder = rustls::ServerConfig::builder();
+    let mut tls_config = match client_verifier(server_config)? {
+        Some(verifier) => tls_builder.with_client_cert_verifier(verifier),
+        None => tls_builder.with_no_client_auth(),
+    }
+    .with_single_cert(certs, private_key)?;
+    let agents = Arc::new(AgentMapping::new(server_config));
+    tls_config.alpn_protocols = vec![ALPN_RTA.to_vec(), ALPN_H3.to_vec()];
+    tls_config.session_storage = Arc::new(CountingSessionStore::new(
+        server_config.session_cache_size,
+        state.metrics.clone(),
+    ));
+    if server_config.early_data {
+        // QUIC accepts early data only with the maximum size; its flow control limits it.
+        tls_config.max_early_data_size = u32::MAX;
+    }
     let quic_crypto = quinn::crypto::rustls::QuicServerConfig::try_from(tls_config)?;
-    let quic_server_config = quinn::ServerConfig::with_crypto(Arc::new(quic_crypto));
+    let quic_crypto = CountingServerConfig::new(quic_crypto, state.metrics.clone());
+    let mut quic_server_config = quinn::ServerConfig::with_crypto(Arc::new(quic_crypto));
+
+    // Sessions persist while idle; clients keep them alive within the idle timeout.
+    let mut transport = quinn::TransportConfig::default();
+    let max_streams = VarInt::from_u32(server_config.max_concurrent_streams);
+    transport
+        .max_concurrent_bidi_streams(max_streams)
+        .max_concurrent_uni_streams(max_streams)
+        .max_idle_timeout(Some(Duration::from_secs(server_config.idle_timeout_secs).try_into()?));
+    quic_server_config.transport_config(Arc::new(transport));
 
     // Bind the QUIC endpoint.
-    let mut endpoint = Endpoint::server(quic_server_config, addr)?;
+    let endpoint = Endpoint::server(quic_server_config, addr)?;
     info!("QUIC Token Exchange endpoint listening on {}", addr);
 
     // Process incoming connections.
-    while let Some(connecting) = endpoint.accept().await {
-        // Clone the token configuration and the IdP providers to satisfy the 'static lifetime.
-        let token_config_cloned = token_config.clone();
-        let idp_providers_cloned = idp_providers.clone();
+    let early_data = server_config.early_data;
+    while let Some(incoming) = endpoint.accept().await {
+        let state = state.clone();
+        let agents = agents.clone();
         tokio::spawn(async move {
-            match connecting.await {
-                Ok(conn) => {
-                    info!("Established connection from {}", conn.remote_address());
-                    if let Err(e) = handle_exchange_connection(conn, &token_config_cloned, &idp_providers_cloned).await {
-                        error!("Error processing exchange connection: {:?}", e);
-                    }
-                }
-                Err(e) => error!("Connection failed: {:?}", e),
+            let remote = incoming.remote_address();
+            if let Err(e) = serve_connection(incoming, early_data, agents, state).await {
+                error!("Error processing connection from {}: {:?}", remote, e);
             }
         });
     }
     Ok(())
 }
 
-/// Processes a single token exchange connection.
+/// Accepts a connection and serves it the protocol it negotiated by ALPN.
+///
+/// With `early_data`, a connection of the framed protocol is served as soon as the
+/// client's first flight has arrived, so that requests it sent in 0-RTT early data are
+/// read before the handshake completes. HTTP/3 connections are served once their
+/// handshake is complete.
+async fn serve_connection(
+    incoming: quinn::Incoming,
+    early_data: bool,
+    agents: Arc<AgentMapping>,
+    state: Arc<AppState>,
+) -> Result<()> {
+    let mut connecting = incoming.accept()?;
+    state.metrics.connection_accepted();
+    let protocol = connecting.handshake_data().await?
+        .downcast::<quinn::crypto::rustls::HandshakeData>()
+        .ok()
+        .and_then(|data| data.protocol);
+    info!(
+        "Accepted connection from {} ({})",
+        connecting.remote_address(),
+        String::from_utf8_lossy(protocol.as_deref().unwrap_or(ALPN_RTA)),
+    );
+    match protocol.as_deref() {
+        Some(ALPN_H3) => handle_h3_connection(connecting.await?, &agents, state).await,
+        // Servers can always accept 0-RTT; whether the client sent any is up to it.
+        _ if early_data => match connecting.into_0rtt() {
+            Ok((conn, accepted)) => handle_exchange_connection(conn, Some(accepted), agents, state).await,
+            Err(connecting) => handle_exchange_connection(connecting.await?, None, agents, state).await,
+        },
+        _ => handle_exchange_connection(connecting.await?, None, agents, state).await,
+    }
+}
+
+/// Confirms the completed handshake of `session`'s connection, authenticating the agent
+/// by its client certificate. A certificate that names no agent closes the connection.
+pub(crate) fn confirm_handshake(session: &ConnectionSession, agents: &AgentMapping) -> Result<()> {
+    let conn = session.connection();
+    let agent_id = match agents.agent_for(conn) {
+        Ok(agent_id) => agent_id,
+        Err(e) => {
+            conn.close(ExchangeErrorCode::ClientAuthFailed.into(), b"unusable client certificate");
+            return Err(e.context(format!("Refusing client certificate of {}", conn.remote_address())));
+        }
+    };
+    session.confirm(agent_id.clone())?;
+    info!(
+        "Established connection from {} (agent {})",
+        conn.remote_address(),
+        agent_id.as_deref().unwrap_or("unauthenticated"),
+    );
+    Ok(())
+}
+
+/// Runs the session loop of a client connection until the connection closes.
+///
+/// Bidirectional streams carry requests and their responses, unidirectional streams
+/// one-way messages from the client. Every stream is served by its own task, so a
+/// slow request (e.g. an exchange waiting on the IdP) does not hold up the others.
+/// Another task pushes events concerning the connection's sessions to the client on
+/// control streams (see [`crate::infrastructure::push`]).
+/// State tied to the connection, such as its binding material and the sessions
+/// established on it, lives in a [`ConnectionSession`] shared by those tasks. If a
+/// request fails, its stream ends with an [`ErrorResponse`] and the matching
+/// [`ExchangeErrorCode`] (see [`fail_stream`]); the connection stays open.
 ///
-/// Accepts a bidirectional QUIC stream, reads and parses a token exchange request,
-/// validates the custom grant type, and calls the application command handler
-/// to issue an RTAToken. Finally, it serializes and encodes the token as Base64,
-/// sending it back to the client as a JSON response.
+/// The agent authenticated by the client certificate, if any, is looked up in
+/// `agents`; tokens requested on the connection can only be issued to it.
+///
+/// `handshake` is set if the connection is served before its handshake completes, to
+/// read requests sent in 0-RTT early data. Those that are replay-safe are answered
+/// right away; the others wait until the handshake is confirmed (see
+/// [`StreamRequest::replay_safe`]).
 async fn handle_exchange_connection(
     conn: Connection,
-    token_config: &TokenConfig,
-    idp_providers: &IdpProviders, // New parameter for IdP selection
+    handshake: Option<ZeroRttAccepted>,
+    agents: Arc<AgentMapping>,
+    state: Arc<AppState>,
 ) -> Result<()> {
-    // Accept a bidirectional stream.
-    let (mut send, mut recv) = conn.accept_bi().await?;
-    
-    // Read the request into a buffer (assume the request fits within 4KB).
-    let mut buf = vec![0u8; 4096];
-    let n = recv.read(&mut buf).await?
-        .ok_or_else(|| anyhow!("Stream closed prematurely"))?;
-    buf.truncate(n);
-
-    // Parse the JSON request.
-    let req: TokenExchangeRequest = serde_json::from_slice(&buf)
-        .map_err(|e| anyhow!("Failed to parse token exchange request: {:?}", e))?;
-    
-    // Validate the custom grant type.
-    if req.grant_type != "urn:ietf:params:oauth:grant-type:rta_token_exchange" {
-        let msg = "Unsupported grant type";
-        send.write_all(msg.as_bytes()).await?;
-        send.finish()?;
-        return Err(anyhow!(msg));
-    }
-    
-    // Build the command to issue a token, including the provider field.
-    let cmd = IssueTokenCommand {
-        oauth_token: req.oauth_token,
-        agent_id: req.agent_id,
-        provider: req.provider, // Passed from the request (if provided)
+    let session = Arc::new(ConnectionSession::new(conn.clone()));
+    let mut streams = JoinSet::new();
+    match handshake {
+        Some(accepted) => {
+            let session = session.clone();
+            streams.spawn(async move {
+                // Resolves once the handshake completes or fails; on servers, its value
+                // only tells whether the client sent early data.
+                accepted.await;
+                if session.connection().close_reason().is_some() {
+                    return;
+                }
+                if let Err(e) = confirm_handshake(&session, &agents) {
+                    error!("Failed to confirm handshake: {:?}", e);
+                }
+            });
+        }
+        None => confirm_handshake(&session, &agents)?,
+    }
+    let (acks, acks_rx) = mpsc::channel(ACK_QUEUE_LEN);
+    streams.spawn(run_push_channel(session.clone(), state.clone(), state.events.subscribe(), acks_rx));
+    let closed = loop {
+        tokio::select! {
+            bi = conn.accept_bi() => match bi {
+                Ok((send, recv)) => {
+                    streams.spawn(serve_request_stream(session.clone(), state.clone(), send, recv));
+                }
+                Err(e) => break e,
+            },
+            uni = conn.accept_uni() => match uni {
+                Ok(recv) => {
+                    streams.spawn(serve_one_way_stream(session.clone(), acks.clone(), recv));
+                }
+                Err(e) => break e,
+            },
+            // Reap finished streams so the set does not grow over the connection's life.
+            Some(joined) = streams.join_next(), if !streams.is_empty() => {
+                if let Err(e) = joined {
+                    error!("Stream task on connection from {} failed: {:?}", conn.remote_address(), e);
+                }
+            }
+        }
     };
-    
-    // In a real system, context data could come from IdP introspection; we use a static value here.
-    let context_data = b"introspection_based_context";
-    let (token, _event) = handle_issue_token(cmd, context_data, idp_providers).await?;
-    
-    // Serialize the RTAToken and encode it in Base64 using the new engine API.
-    let token_bytes = token.serialize()?;
-    let encoded_token = STANDARD.encode(&token_bytes);
-
-    // Build the JSON response.
-    let resp = TokenExchangeResponse { rtatoken: encoded_token };
-    let resp_json = serde_json::to_vec(&resp)?;
-    send.write_all(&resp_json).await?;
+    // Streams still open can no longer complete.
+    streams.shutdown().await;
+    info!(
+        "Connection from {} closed ({}); {} session(s) established on it",
+        conn.remote_address(),
+        closed,
+        session.sessions().len(),
+    );
+    match closed {
+        quinn::ConnectionError::ApplicationClosed(_) | quinn::ConnectionError::TimedOut => Ok(()),
+        e => Err(e.into()),
+    }
+}
+
+/// Serves the requests on a bidirectional stream.
+async fn serve_request_stream(
+    session: Arc<ConnectionSession>,
+    state: Arc<AppState>,
+    mut send: SendStream,
+    mut recv: RecvStream,
+) {
+    if let Err(e) = process_stream(&session, &mut send, &mut recv, &state).await {
+        error!("Request from {} failed: {:?}", session.client_ip(), e);
+        fail_stream(&mut send, &mut recv, &e).await;
+    }
+}
+
+/// Applies the one-way messages on a unidirectional stream. There is nowhere to
+/// report a failure, so the stream is stopped with its error code instead.
+async fn serve_one_way_stream(session: Arc<ConnectionSession>, acks: mpsc::Sender<PushAck>, mut recv: RecvStream) {
+    if let Err(e) = process_one_way_stream(&session, &acks, &mut recv).await {
+        error!("One-way message from {} failed: {:?}", session.client_ip(), e);
+        let _ = recv.stop(ExchangeErrorCode::for_error(&e).into());
+    }
+}
+
+/// Ends a stream whose request failed with `err`.
+///
+/// The error response is written and the send side finished, so that the client
+/// receives it in full; the client's side is stopped with the error code, discarding
+/// any requests it queued behind the failed one. If the response cannot be written,
+/// the send side is reset with the same code instead.
+async fn fail_stream(send: &mut SendStream, recv: &mut RecvStream, err: &anyhow::Error) {
+    let code = ExchangeErrorCode::for_error(err);
+    let _ = recv.stop(code.into());
+    let response = ErrorResponse::for_error(code, err);
+    let written = FrameCodec::default().write_message(send, &response).await.is_ok();
+    if !written || send.finish().is_err() {
+        // The stream may already be finished or reset, in which case this is a no-op.
+        let _ = send.reset(code.into());
+    }
+}
+
+/// Answers request frames on one stream, in order, until the client finishes its
+/// send side; then finishes the response side.
+async fn process_stream(
+    session: &ConnectionSession,
+    send: &mut SendStream,
+    recv: &mut RecvStream,
+    state: &AppState,
+) -> Result<()> {
+    let codec = FrameCodec::default();
+    while let Some(frame) = codec.read_frame(recv).await? {
+        let request = StreamRequest::from_frame(&frame)?;
+        if !session.is_confirmed() {
+            // Only streams opened in 0-RTT carry early data; others merely arrived
+            // before the confirmation was recorded.
+            if recv.is_0rtt() {
+                state.metrics.early_request(request.replay_safe());
+            }
+            if !request.replay_safe() {
+                session.confirmed().await?;
+            }
+        }
+        match request {
+            StreamRequest::Exchange(req) => process_exchange(session, send, *req, state).await?,
+            StreamRequest::Introspection(req) => process_introspection(send, req, state).await?,
+            StreamRequest::Challenge(_) => process_challenge(session, send, state).await?,
+            StreamRequest::Authorize(req) => process_authorize(session, send, req, state).await?,
+            StreamRequest::Refresh(req) => process_refresh(session, send, req, state).await?,
+            StreamRequest::Revocation(req) => process_revocation(session, send, req, state).await?,
+        }
+    }
     send.finish()?;
-    
+    Ok(())
+}
+
+/// Applies one-way messages, in order, until the client finishes the stream.
+async fn process_one_way_stream(
+    session: &ConnectionSession,
+    acks: &mpsc::Sender<PushAck>,
+    recv: &mut RecvStream,
+) -> Result<()> {
+    let codec = FrameCodec::default();
+    while let Some(frame) = codec.read_frame(recv).await? {
+        match OneWayMessage::from_frame(&frame)? {
+            OneWayMessage::PostureReport(report) => {
+                session.report_posture(report.device_posture);
+                info!("Device posture reported by {}: {:?}", session.client_ip(), report.device_posture);
+            }
+            // The push task is gone only once the connection is closing.
+            OneWayMessage::PushAck(ack) => {
+                let _ = acks.send(ack).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Answers a token exchange request.
+async fn process_exchange(
+    session: &ConnectionSession,
+    send: &mut SendStream,
+    req: TokenExchangeRequest,
+    state: &AppState,
+) -> Result<()> {
+    let resp = exchange_token(session, req, state).await?;
+    FrameCodec::default().write_message(send, &resp).await?;
+    Ok(())
+}
+
+/// Answers a token exchange request, RTA or RFC 8693, once it has redeemed its
+/// nonce. Shared by the raw QUIC protocol and the HTTP/3 token endpoint.
+pub(crate) async fn exchange_token(
+    session: &ConnectionSession,
+    req: TokenExchangeRequest,
+    state: &AppState,
+) -> Result<TokenExchangeResponse> {
+    // Every exchange must redeem a fresh challenge issued on this connection.
+    let nonce: [u8; NONCE_LEN] = req.nonce.as_deref()
+        .and_then(|n| STANDARD.decode(n).ok())
+        .and_then(|n| n.try_into().ok())
+        .ok_or(NonceError::Unknown)?;
+    state.nonces.consume(&nonce, &session.binding_hash(), unix_now())?;
+
+    if req.grant_type == GRANT_TYPE_TOKEN_EXCHANGE {
+        let req = standard_exchange::into_rta_request(req, session, state)?;
+        let issued = grant(session, req, state).await?;
+        return Ok(standard_exchange::response(&issued, state));
+    }
+    let mirror = req.jwt;
+    Ok(match grant(session, req, state).await? {
+        Issued::Token(token) => {
+            let jwt = if mirror {
+                Some(mint_jwt(&token, token.audience(), None, state)?.jwt)
+            } else {
+                None
+            };
+            // Encode the RTAToken in its canonical binary layout, then as Base64.
+            let rtatoken = Some(STANDARD.encode(token.encode()));
+            TokenExchangeResponse { rtatoken, jwt, ..Default::default() }
+        }
+        Issued::Jwt(jwt) => TokenExchangeResponse { jwt: Some(jwt.jwt), ..Default::default() },
+    })
+}
+
+/// What an RTA grant issued.
+pub(crate) enum Issued {
+    /// A new RTAToken.
+    Token(RTAToken),
+    /// A JWT translated from a presented RTAToken.
+    Jwt(MintedJwt),
+}
+
+/// Dispatches an RTA grant on its grant type and calls the application command handler.
+async fn grant(session: &ConnectionSession, req: TokenExchangeRequest, state: &AppState) -> Result<Issued> {
+    let exported = session.exported();
+    // Scopes may also be given space-delimited, as in OAuth.
+    let scopes: Option<BTreeSet<String>> = req.scopes
+        .map(|s| s.into_iter().collect())
+        .or_else(|| req.scope.map(|s| s.split_whitespace().map(str::to_owned).collect()));
+    let (token, event) = match req.grant_type.as_str() {
+        GRANT_TYPE_EXCHANGE => {
+            // Build the command to issue a token, including the provider field.
+            let cmd = IssueTokenCommand {
+                oauth_token: req.oauth_token,
+                agent_id: session.issuable_agent(req.agent_id)?,
+                provider: req.provider, // Passed from the request (if provided)
+                scopes,
+                client_network: Some(session.client_ip()),
+                device_posture: req.device_posture,
+                audience: req.audience,
+                connection_binding: Some(exported),
+                attenuable: req.attenuable,
+            };
+            // The token is bound to the context built from IdP introspection and this connection.
+            handle_issue_token(cmd, state).await?
+        }
+        GRANT_TYPE_DELEGATION => {
+            let parent = req.parent_token.as_deref()
+                .ok_or_else(|| RequestError::Invalid("delegation requires a parent_token".into()))?;
+            let parent_token = decode_token(parent)?;
+            // The certificate authenticates the delegator, who holds the parent token;
+            // the child is handed to another agent, named freely.
+            session.check_holder(parent_token.agent_id())?;
+            if req.agent_id.is_empty() {
+                return Err(RequestError::Invalid("delegation requires an agent_id".into()).into());
+            }
+            let cmd = DelegateTokenCommand {
+                parent_token,
+                parent_binding: Some(session.binding_evidence(binding_proof(req.binding_proof.as_deref())?)),
+                agent_id: req.agent_id,
+                scopes,
+                max_depth: req.max_depth,
+                client_network: Some(session.client_ip()),
+                device_posture: req.device_posture,
+                audience: req.audience,
+                attenuable: req.attenuable,
+            };
+            handle_delegate_token(cmd, state).await?
+        }
+        GRANT_TYPE_JWT_TRANSLATION => {
+            let presented = req.rtatoken.as_deref()
+                .ok_or_else(|| RequestError::Invalid("JWT translation requires an rtatoken".into()))?;
+            let token = decode_token(presented)?;
+            let cmd = TranslateTokenCommand {
+                token: token.clone(),
+                binding: Some(session.binding_evidence(binding_proof(req.binding_proof.as_deref())?)),
+                audience: req.audience
+                    .ok_or_else(|| RequestError::Invalid("JWT translation requires an audience".into()))?,
+                scopes,
+            };
+            let jwt = handle_translate_token(cmd, state).await?;
+            info!("Translated RTAToken to JWT for agent: {}", token.agent_id().unwrap_or("-"));
+            return Ok(Issued::Jwt(jwt));
+        }
+        other => return Err(RequestError::UnsupportedGrantType(other.to_string()).into()),
+    };
+    session.attach(token.session_id);
+    state.publish([event]);
+
     let session_id_hex = hex::encode(token.session_id);
-    info!("Issued token for session_id: {}", session_id_hex);
+    info!(
+        "Issued token for session_id: {} agent: {} (key ID {})",
+        session_id_hex,
+        token.agent_id().unwrap_or("-"),
+        token.key_id(),
+    );
+    Ok(Issued::Token(token))
+}
+
+/// Decodes a Base64-encoded RTAToken presented in a request.
+pub fn decode_token(encoded: &str) -> Result<RTAToken> {
+    Ok(RTAToken::decode_base64(encoded)?)
+}
+
+/// Decodes the optional Base64 proof of a presented token's binding to another
+/// connection.
+fn binding_proof(proof: Option<&str>) -> Result<Option<[u8; BINDING_LEN]>> {
+    proof
+        .map(|p| -> Result<[u8; BINDING_LEN]> {
+            STANDARD.decode(p)
+                .ok()
+                .and_then(|p| p.try_into().ok())
+                .ok_or_else(|| RequestError::Invalid(format!("binding_proof must be {} Base64-encoded bytes", BINDING_LEN)).into())
+        })
+        .transpose()
+}
+
+/// Answers an introspection request from an authenticated resource server.
+async fn process_introspection(send: &mut SendStream, req: IntrospectionRequest, state: &AppState) -> Result<()> {
+    authenticate_client(state, &req.client_id, &req.client_secret)?;
+    let request = AccessRequest {
+        resource: req.resource.as_deref(),
+        action: req.action.as_deref(),
+        client_ip: req.client_ip,
+    };
+    // A token that is not even Base64 is simply inactive.
+    let encoded = token::decode_base64(req.token.trim()).unwrap_or_default();
+    let introspection = introspect_token(&encoded, state, &request);
+    FrameCodec::default().write_message(send, &introspection).await?;
+    info!("Introspected token for client {}: active={}", req.client_id, introspection.active);
+    Ok(())
+}
+
+/// Issues a single-use nonce bound to the connection.
+async fn process_challenge(session: &ConnectionSession, send: &mut SendStream, state: &AppState) -> Result<()> {
+    let nonce = state.nonces.issue(session.binding_hash(), unix_now())?;
+    let resp = ChallengeResponse {
+        nonce: STANDARD.encode(nonce),
+        expires_in: state.nonces.ttl_secs(),
+    };
+    FrameCodec::default().write_message(send, &resp).await?;
+    Ok(())
+}
+
+/// Decides whether the holder of a token may perform the requested access now, in
+/// the request's single round trip.
+///
+/// The token must validate against its session's current state and be presented by
+/// its holder; the PDP then decides on the access (see [`authorize_action`]). A token
+/// that does not validate is a denial, not a failed request. So is a PDP that cannot
+/// decide: access fails closed.
+///
+/// In early data, only a token bound to no connection is authorized right away; the
+/// holder of a bound token is recognized by the handshake's binding material, so its
+/// authorization waits for the handshake.
+async fn process_authorize(
+    session: &ConnectionSession,
+    send: &mut SendStream,
+    req: AuthorizeRequest,
+    state: &AppState,
+) -> Result<()> {
+    let proof = binding_proof(req.binding_proof.as_deref())?;
+    let decided = match RTAToken::decode_base64(&req.rtatoken) {
+        Ok(token) => {
+            if token.connection_binding().is_some() {
+                session.confirmed().await?;
+            }
+            let binding = session.is_confirmed().then(|| session.binding_evidence(proof));
+            let client_ip = Some(session.client_ip());
+            authorize_action(&token, binding.as_ref(), &req.action, &req.resource, &req.attributes, client_ip, state).await
+                .unwrap_or_else(|e| {
+                    error!("Policy evaluation for {} failed: {:?}", session.client_ip(), e);
+                    PolicyDecision { reason: Some("policy decision unavailable".into()), ..Default::default() }
+                })
+        }
+        Err(e) => PolicyDecision { reason: Some(e.to_string()), ..Default::default() },
+    };
+    let resp = AuthorizeResponse {
+        decision: if decided.permit { Decision::Permit } else { Decision::Deny },
+        reason: decided.reason.filter(|_| !decided.permit),
+        obligations: decided.obligations,
+    };
+    FrameCodec::default().write_message(send, &resp).await?;
+    info!(
+        "Authorization of {} on {} for {}: {:?}",
+        req.action,
+        req.resource,
+        session.client_ip(),
+        resp.decision,
+    );
+    Ok(())
+}
+
+/// Re-issues the token of a session whose holder is on this connection: the one the
+/// session was established on, or another one with proof of the token's binding.
+///
+/// The session is rebound to its context as it stands now: the requested or last
+/// reported device posture, the client's current address and the active policy
+/// version. The presented token may have expired or no longer match the session's
+/// context, but must be of its latest generation.
+async fn process_refresh(
+    session: &ConnectionSession,
+    send: &mut SendStream,
+    req: RefreshRequest,
+    state: &AppState,
+) -> Result<()> {
+    let token = decode_token(&req.rtatoken)?;
+    let binding = session.binding_evidence(binding_proof(req.binding_proof.as_deref())?);
+    let current = validate_token_holder(&token, &binding, state)?;
+    let device_posture = req.device_posture
+        .or_else(|| session.reported_posture())
+        .unwrap_or(current.context.device_posture);
+    let context = AuthContext {
+        client_network: Some(session.client_ip()),
+        device_posture,
+        policy_version: state.policy_version(),
+        ..current.context
+    };
+    let cmd = RefreshTokenCommand { session_id: token.session_id, context };
+    let (token, event) = handle_refresh_token(cmd, state).await?;
+    // Pushes for the session now go to the connection its holder refreshed on.
+    session.attach(token.session_id);
+    state.publish([event]);
+    let resp = RefreshResponse {
+        rtatoken: STANDARD.encode(token.encode()),
+        generation: token.generation(),
+        expires_in: state.token_config.max_age_secs,
+    };
+    FrameCodec::default().write_message(send, &resp).await?;
+    info!(
+        "Refreshed token for session_id: {} (generation {})",
+        hex::encode(token.session_id),
+        resp.generation,
+    );
+    Ok(())
+}
+
+/// Revokes the session of a token presented by its holder, with the sessions delegated
+/// from it, and pushes the revocation to their holders.
+///
+/// The presented token may have expired or gone stale: giving up a session needs only
+/// proof of holding it.
+async fn process_revocation(
+    session: &ConnectionSession,
+    send: &mut SendStream,
+    req: RevocationRequest,
+    state: &AppState,
+) -> Result<()> {
+    let token = decode_token(&req.token)?;
+    let binding = session.binding_evidence(binding_proof(req.binding_proof.as_deref())?);
+    if validate_revocation(&token, &binding, state)?.is_some() {
+        let cmd = RevokeTokenCommand { session_id: hex::encode(token.session_id) };
+        let events = handle_revoke_token(cmd, state).await?;
+        info!(
+            "Revoked session_id: {} with {} delegated session(s)",
+            hex::encode(token.session_id),
+            events.len().saturating_sub(1),
+        );
+        state.publish(events);
+    }
+    FrameCodec::default().write_message(send, &RevocationResponse {}).await?;
     Ok(())
 }
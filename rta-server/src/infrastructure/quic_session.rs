@@ -0,0 +1,213 @@
+// src/infrastructure/quic_session.rs
+use anyhow::{anyhow, Result};
+use quinn::Connection;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::pin::pin;
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::Notify;
+use crate::domain::binding::{binding_hash, BindingEvidence, EXPORTER_LABEL, BINDING_LEN};
+use crate::domain::context::DevicePosture;
+use crate::infrastructure::client_auth::AgentMismatch;
+
+/// Server-side state of one client connection, held for the connection's lifetime and
+/// shared by the tasks serving its streams.
+///
+/// A connection accepting 0-RTT early data is served before its handshake completes.
+/// What only the completed handshake establishes, the binding material and the
+/// authenticated agent, is recorded by [`ConnectionSession::confirm`]; requests that
+/// depend on it wait for [`ConnectionSession::confirmed`] first.
+#[derive(Debug)]
+pub struct ConnectionSession {
+    conn: Connection,
+    handshake: OnceLock<Handshake>,
+    confirmed: Notify,
+    /// RTA sessions held on this connection: established or last refreshed on it.
+    sessions: Mutex<HashSet<[u8; 16]>>,
+    /// Posture most recently reported by the agent on a one-way stream.
+    device_posture: Mutex<Option<DevicePosture>>,
+}
+
+#[derive(Debug)]
+struct Handshake {
+    /// Keying material exported from the connection; bound into every token issued on it.
+    exported: [u8; BINDING_LEN],
+    /// Agent authenticated by the client certificate, if the client presented one.
+    agent_id: Option<String>,
+}
+
+impl Handshake {
+    fn issuable_agent(&self, claimed: String) -> Result<String, AgentMismatch> {
+        match &self.agent_id {
+            None => Ok(claimed),
+            Some(certified) if claimed.is_empty() || claimed == *certified => Ok(certified.clone()),
+            Some(certified) => Err(AgentMismatch { claimed, certified: certified.clone() }),
+        }
+    }
+
+    fn check_holder(&self, agent_id: Option<&str>) -> Result<(), AgentMismatch> {
+        match &self.agent_id {
+            Some(certified) if agent_id != Some(certified.as_str()) => Err(AgentMismatch {
+                claimed: agent_id.unwrap_or_default().to_owned(),
+                certified: certified.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ConnectionSession {
+    pub fn new(conn: Connection) -> Self {
+        Self {
+            conn,
+            handshake: OnceLock::new(),
+            confirmed: Notify::new(),
+            sessions: Mutex::new(HashSet::new()),
+            device_posture: Mutex::new(None),
+        }
+    }
+
+    /// Records the completed handshake: exports the connection's binding material and
+    /// takes note of the agent authenticated by the client certificate.
+    ///
+    /// Clients derive the same binding material from their side of the handshake with
+    /// the same label and an empty context.
+    pub fn confirm(&self, agent_id: Option<String>) -> Result<()> {
+        let mut exported = [0u8; BINDING_LEN];
+        self.conn.export_keying_material(&mut exported, EXPORTER_LABEL, b"")
+            .map_err(|e| anyhow!("Failed to export connection keying material: {:?}", e))?;
+        self.handshake.set(Handshake { exported, agent_id })
+            .map_err(|_| anyhow!("Handshake confirmed twice"))?;
+        self.confirmed.notify_waiters();
+        Ok(())
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        self.handshake.get().is_some()
+    }
+
+    /// Waits until the handshake is confirmed; fails if the connection closes first.
+    pub async fn confirmed(&self) -> Result<()> {
+        let mut notified = pin!(self.confirmed.notified());
+        notified.as_mut().enable();
+        if self.is_confirmed() {
+            return Ok(());
+        }
+        tokio::select! {
+            _ = notified => Ok(()),
+            closed = self.conn.closed() => Err(closed.into()),
+        }
+    }
+
+    /// What the completed handshake established; only requests that waited for
+    /// [`ConnectionSession::confirmed`] may depend on it.
+    fn handshake(&self) -> &Handshake {
+        self.handshake.get().expect("request served before the handshake was confirmed")
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    pub fn exported(&self) -> [u8; BINDING_LEN] {
+        self.handshake().exported
+    }
+
+    /// Hash of the binding material, as signed into tokens and nonces.
+    pub fn binding_hash(&self) -> [u8; 32] {
+        binding_hash(&self.handshake().exported)
+    }
+
+    /// Current address of the client; may change if the client migrates.
+    pub fn client_ip(&self) -> IpAddr {
+        self.conn.remote_address().ip()
+    }
+
+    pub fn agent_id(&self) -> Option<&str> {
+        self.handshake().agent_id.as_deref()
+    }
+
+    /// The agent a token requested on this connection is issued to: the `claimed` one,
+    /// which must be the certificate's agent if the client authenticated with one. An
+    /// empty claim stands for the certificate's agent.
+    pub fn issuable_agent(&self, claimed: String) -> Result<String, AgentMismatch> {
+        self.handshake().issuable_agent(claimed)
+    }
+
+    /// Checks that a token presented on this connection was issued to the agent of the
+    /// client certificate, if the client authenticated with one.
+    pub fn check_holder(&self, agent_id: Option<&str>) -> Result<(), AgentMismatch> {
+        self.handshake().check_holder(agent_id)
+    }
+
+    /// Evidence that a token presented on this connection is held by the client, with
+    /// an optional proof of its binding to another connection.
+    pub fn binding_evidence(&self, proof: Option<[u8; BINDING_LEN]>) -> BindingEvidence {
+        BindingEvidence { exported: self.handshake().exported, proof }
+    }
+
+    /// Records a session whose tokens were issued, or refreshed, on this connection.
+    pub fn attach(&self, session_id: [u8; 16]) {
+        self.sessions.lock().expect("session set lock poisoned").insert(session_id);
+    }
+
+    /// Whether `session_id` is held on this connection.
+    pub fn owns(&self, session_id: &[u8; 16]) -> bool {
+        self.sessions.lock().expect("session set lock poisoned").contains(session_id)
+    }
+
+    /// Sessions held on this connection.
+    pub fn sessions(&self) -> Vec<[u8; 16]> {
+        self.sessions.lock().expect("session set lock poisoned").iter().copied().collect()
+    }
+
+    pub fn report_posture(&self, posture: DevicePosture) {
+        *self.device_posture.lock().expect("posture lock poisoned") = Some(posture);
+    }
+
+    pub fn reported_posture(&self) -> Option<DevicePosture> {
+        *self.device_posture.lock().expect("posture lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handshake(agent_id: Option<&str>) -> Handshake {
+        Handshake { exported: [0; BINDING_LEN], agent_id: agent_id.map(str::to_owned) }
+    }
+
+    #[test]
+    fn without_certificate_any_agent_is_issuable() {
+        let anonymous = handshake(None);
+        assert_eq!(anonymous.issuable_agent("copilot".into()).unwrap(), "copilot");
+        assert_eq!(anonymous.issuable_agent(String::new()).unwrap(), "");
+    }
+
+    #[test]
+    fn certified_agent_is_issued_for_matching_or_empty_claims() {
+        let certified = handshake(Some("copilot"));
+        assert_eq!(certified.issuable_agent("copilot".into()).unwrap(), "copilot");
+        assert_eq!(certified.issuable_agent(String::new()).unwrap(), "copilot");
+    }
+
+    #[test]
+    fn claim_for_another_agent_is_rejected() {
+        let err = handshake(Some("copilot")).issuable_agent("mallory".into()).unwrap_err();
+        assert_eq!((err.claimed.as_str(), err.certified.as_str()), ("mallory", "copilot"));
+    }
+
+    #[test]
+    fn holder_must_be_the_certified_agent() {
+        assert!(handshake(None).check_holder(Some("copilot")).is_ok());
+        assert!(handshake(None).check_holder(None).is_ok());
+
+        let certified = handshake(Some("copilot"));
+        assert!(certified.check_holder(Some("copilot")).is_ok());
+        assert!(certified.check_holder(Some("mallory")).is_err());
+        // A token naming no agent is not held by the certified one either.
+        let err = certified.check_holder(None).unwrap_err();
+        assert_eq!((err.claimed.as_str(), err.certified.as_str()), ("", "copilot"));
+    }
+}
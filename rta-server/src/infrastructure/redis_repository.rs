@@ -14,17 +14,16 @@ impl TokenRepository {
     }
 
     pub async fn save(&self, token: &RTAToken) -> Result<()> {
-        let mut conn = self.client.get_async_connection().await?;
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
         let session_id_hex = hex::encode(token.session_id);
-        let token_bytes = bincode::serialize(token)?;
-        conn.set(format!("rtatoken:{}", session_id_hex), token_bytes).await?;
+        conn.set::<_, _, ()>(format!("rtatoken:{}", session_id_hex), token.encode().as_slice()).await?;
         Ok(())
     }
 
     pub async fn get(&self, session_id_hex: &str) -> Result<RTAToken> {
-        let mut conn = self.client.get_async_connection().await?;
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
         let token_bytes: Vec<u8> = conn.get(format!("rtatoken:{}", session_id_hex)).await?;
-        let token = bincode::deserialize(&token_bytes)?;
+        let token = RTAToken::decode(&token_bytes)?;
         Ok(token)
     }
 }
@@ -0,0 +1,310 @@
+// src/infrastructure/session_store.rs
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use crate::domain::context::AuthContext;
+use crate::domain::session::Session;
+
+/// In-memory store of active RTA sessions, keyed by session ID.
+///
+/// Delegated sessions are indexed under their parent so that revoking a session
+/// revokes its whole subtree. Sessions are kept for `ttl_secs` after their latest
+/// token was issued.
+#[derive(Debug)]
+pub struct SessionStore {
+    sessions: DashMap<[u8; 16], Session>,
+    children: DashMap<[u8; 16], Vec<[u8; 16]>>,
+    ttl_secs: u64,
+}
+
+impl SessionStore {
+    pub fn new(ttl_secs: u64) -> Self {
+        Self { sessions: DashMap::new(), children: DashMap::new(), ttl_secs }
+    }
+
+    pub fn insert(&self, session: Session) {
+        self.sessions.insert(session.session_id, session);
+    }
+
+    /// Records a delegated session under its parent.
+    ///
+    /// Returns false, without inserting, if the parent is unknown or has been revoked.
+    /// The parent's child list stays locked while the parent is checked, so a
+    /// concurrent [`SessionStore::revoke`] either sees the new child or prevents it.
+    pub fn insert_child(&self, session: Session) -> bool {
+        let Some(parent_id) = session.parent else {
+            return false;
+        };
+        let mut siblings = self.children.entry(parent_id).or_default();
+        let parent_live = self.sessions.get(&parent_id).is_some_and(|p| !p.revoked);
+        if !parent_live {
+            return false;
+        }
+        siblings.push(session.session_id);
+        self.sessions.insert(session.session_id, session);
+        true
+    }
+
+    pub fn get(&self, session_id: &[u8; 16]) -> Option<Session> {
+        self.sessions.get(session_id).map(|s| s.clone())
+    }
+
+    /// Rebinds a live session to `context` and advances its generation, for a token
+    /// issued at `issued_at`.
+    ///
+    /// Returns the updated session, or `None`, leaving the session unchanged, if the
+    /// session is unknown or revoked or its generations are exhausted.
+    pub fn advance(&self, session_id: &[u8; 16], context: AuthContext, issued_at: u64) -> Option<Session> {
+        let mut session = self.sessions.get_mut(session_id)?;
+        if session.revoked {
+            return None;
+        }
+        let generation = session.generation.checked_add(1)?;
+        session.context = context;
+        session.generation = generation;
+        session.issued_at = issued_at;
+        Some(session.clone())
+    }
+
+    /// Rebinds a live session to `context` without advancing its generation.
+    ///
+    /// The session's current token stops validating, but can still be refreshed by its
+    /// holder. Returns `None` if the session is unknown or revoked.
+    pub fn rebind(&self, session_id: &[u8; 16], context: AuthContext) -> Option<Session> {
+        let mut session = self.sessions.get_mut(session_id)?;
+        if session.revoked {
+            return None;
+        }
+        session.context = context;
+        Some(session.clone())
+    }
+
+    /// Rebinds every live session for which `update` returns a new context, without
+    /// advancing generations; see [`SessionStore::rebind`].
+    pub fn rebind_all(&self, update: impl Fn(&AuthContext) -> Option<AuthContext>) {
+        for mut session in self.sessions.iter_mut() {
+            if session.revoked {
+                continue;
+            }
+            if let Some(context) = update(&session.context) {
+                session.context = context;
+            }
+        }
+    }
+
+    /// Marks a session and all sessions delegated from it, directly or transitively,
+    /// revoked.
+    ///
+    /// Returns the IDs of the sessions that were newly revoked, the given session
+    /// first; empty if the session is unknown or already revoked.
+    pub fn revoke(&self, session_id: &[u8; 16]) -> Vec<[u8; 16]> {
+        let mut revoked = Vec::new();
+        let mut pending = vec![*session_id];
+        while let Some(id) = pending.pop() {
+            match self.sessions.get_mut(&id) {
+                Some(mut session) if !session.revoked => session.revoked = true,
+                _ => continue,
+            }
+            revoked.push(id);
+            if let Some(children) = self.children.get(&id) {
+                pending.extend(children.iter().rev());
+            }
+        }
+        revoked
+    }
+
+    /// Drops sessions, revoked or not, whose latest token was issued `ttl_secs` or
+    /// more before `now`.
+    ///
+    /// A session is kept while sessions delegated from it remain, so that revoking it
+    /// still revokes them; it is dropped with the last of them.
+    pub fn purge_expired(&self, now: u64) {
+        let expired = |session: &Session| session.issued_at.saturating_add(self.ttl_secs) <= now;
+        loop {
+            let candidates: Vec<([u8; 16], Option<[u8; 16]>)> = self.sessions.iter()
+                .filter(|s| expired(s))
+                .map(|s| (s.session_id, s.parent))
+                .collect();
+            let mut purged = false;
+            for (session_id, parent) in candidates {
+                // Holding the child list locks out a concurrent `insert_child`.
+                let removed = match self.children.entry(session_id) {
+                    Entry::Occupied(children) if !children.get().is_empty() => false,
+                    Entry::Occupied(children) => {
+                        let removed = self.sessions.remove_if(&session_id, |_, s| expired(s)).is_some();
+                        if removed {
+                            children.remove();
+                        }
+                        removed
+                    }
+                    Entry::Vacant(_) => self.sessions.remove_if(&session_id, |_, s| expired(s)).is_some(),
+                };
+                if !removed {
+                    continue;
+                }
+                purged = true;
+                if let Some(mut siblings) = parent.and_then(|p| self.children.get_mut(&p)) {
+                    siblings.retain(|id| *id != session_id);
+                }
+            }
+            // Each round drops the sessions whose delegated sessions are gone.
+            if !purged {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::context::{DevicePosture, RiskLevel};
+
+    const TTL: u64 = 100;
+
+    fn context(subject: &str) -> AuthContext {
+        AuthContext {
+            subject: subject.to_string(),
+            agent_id: "agent-1".to_string(),
+            provider: "okta".to_string(),
+            scopes: ["files.read".to_string()].into(),
+            risk_level: RiskLevel::Low,
+            client_network: None,
+            device_posture: DevicePosture::Compliant,
+            policy_version: 1,
+        }
+    }
+
+    fn session(id: u8, issued_at: u64) -> Session {
+        let mut session = Session::new([id; 16], context("alice"), None, None);
+        session.issued_at = issued_at;
+        session
+    }
+
+    fn child(id: u8, parent: u8, issued_at: u64) -> Session {
+        let mut session = session(id, issued_at);
+        session.parent = Some([parent; 16]);
+        session
+    }
+
+    #[test]
+    fn advance_rebinds_and_bumps_generation() {
+        let store = SessionStore::new(TTL);
+        store.insert(session(1, 0));
+
+        let advanced = store.advance(&[1; 16], context("bob"), 50).unwrap();
+        assert_eq!(advanced.generation, 2);
+        assert_eq!(advanced.context.subject, "bob");
+        assert_eq!(advanced.issued_at, 50);
+        assert_eq!(store.get(&[1; 16]), Some(advanced));
+        assert!(store.advance(&[2; 16], context("bob"), 50).is_none());
+    }
+
+    #[test]
+    fn advance_leaves_session_unchanged_when_generations_are_exhausted() {
+        let store = SessionStore::new(TTL);
+        let mut exhausted = session(1, 0);
+        exhausted.generation = u32::MAX;
+        store.insert(exhausted.clone());
+
+        assert!(store.advance(&[1; 16], context("bob"), 50).is_none());
+        assert_eq!(store.get(&[1; 16]), Some(exhausted));
+    }
+
+    #[test]
+    fn revoked_sessions_cannot_advance_or_rebind() {
+        let store = SessionStore::new(TTL);
+        store.insert(session(1, 0));
+
+        assert_eq!(store.revoke(&[1; 16]), vec![[1; 16]]);
+        assert!(store.advance(&[1; 16], context("bob"), 50).is_none());
+        assert!(store.rebind(&[1; 16], context("bob")).is_none());
+        assert!(store.revoke(&[1; 16]).is_empty());
+        assert!(store.revoke(&[9; 16]).is_empty());
+    }
+
+    #[test]
+    fn revocation_cascades_to_delegated_sessions() {
+        let store = SessionStore::new(TTL);
+        store.insert(session(1, 0));
+        assert!(store.insert_child(child(2, 1, 0)));
+        assert!(store.insert_child(child(3, 2, 0)));
+        assert!(store.insert_child(child(4, 1, 0)));
+        store.insert(session(5, 0));
+
+        let revoked = store.revoke(&[2; 16]);
+        assert_eq!(revoked, vec![[2; 16], [3; 16]]);
+        let revoked = store.revoke(&[1; 16]);
+        assert_eq!(revoked, vec![[1; 16], [4; 16]]);
+        assert!(!store.get(&[5; 16]).unwrap().revoked);
+
+        // Nothing can be delegated from a revoked session any more.
+        assert!(!store.insert_child(child(6, 1, 0)));
+        assert!(store.get(&[6; 16]).is_none());
+    }
+
+    #[test]
+    fn revoking_a_root_revokes_the_whole_delegation_tree() {
+        let store = SessionStore::new(TTL);
+        store.insert(session(1, 0));
+        assert!(store.insert_child(child(2, 1, 0)));
+        assert!(store.insert_child(child(3, 2, 0)));
+        assert!(store.insert_child(child(4, 3, 0)));
+        assert!(store.insert_child(child(5, 1, 0)));
+
+        let mut revoked = store.revoke(&[1; 16]);
+        assert_eq!(revoked.first(), Some(&[1; 16]));
+        revoked.sort();
+        assert_eq!(revoked, vec![[1; 16], [2; 16], [3; 16], [4; 16], [5; 16]]);
+        for id in 1..=5 {
+            assert!(store.get(&[id; 16]).unwrap().revoked);
+        }
+        // Nothing can be delegated from a revoked grandchild either.
+        assert!(!store.insert_child(child(6, 4, 0)));
+    }
+
+    #[test]
+    fn purge_drops_sessions_past_their_lifetime() {
+        let store = SessionStore::new(TTL);
+        store.insert(session(1, 0));
+        store.insert(session(2, 50));
+        store.revoke(&[2; 16]);
+
+        store.purge_expired(TTL - 1);
+        assert!(store.get(&[1; 16]).is_some());
+        store.purge_expired(TTL);
+        assert!(store.get(&[1; 16]).is_none());
+        assert!(store.get(&[2; 16]).is_some());
+        store.purge_expired(50 + TTL);
+        assert!(store.get(&[2; 16]).is_none());
+    }
+
+    #[test]
+    fn purge_keeps_refreshed_sessions() {
+        let store = SessionStore::new(TTL);
+        store.insert(session(1, 0));
+        store.advance(&[1; 16], context("alice"), 80).unwrap();
+
+        store.purge_expired(TTL);
+        assert!(store.get(&[1; 16]).is_some());
+    }
+
+    #[test]
+    fn purge_keeps_parents_until_their_delegated_sessions_expire() {
+        let store = SessionStore::new(TTL);
+        store.insert(session(1, 0));
+        assert!(store.insert_child(child(2, 1, 0)));
+        assert!(store.insert_child(child(3, 2, 60)));
+
+        store.purge_expired(TTL);
+        assert!(store.get(&[1; 16]).is_some());
+        assert!(store.get(&[2; 16]).is_some());
+        // Revoking the expired parent still reaches the live grandchild.
+        assert_eq!(store.revoke(&[1; 16]), vec![[1; 16], [2; 16], [3; 16]]);
+
+        store.purge_expired(60 + TTL);
+        for id in 1..=3 {
+            assert!(store.get(&[id; 16]).is_none());
+        }
+        assert!(store.children.is_empty());
+    }
+}
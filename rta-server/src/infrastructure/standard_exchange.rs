@@ -0,0 +1,123 @@
+// src/infrastructure/standard_exchange.rs
+//! RFC 8693 OAuth 2.0 Token Exchange compatibility mode.
+//!
+//! Standard requests are mapped onto the RTA grants by the type of the subject token
+//! and the requested token type:
+//!
+//! | `subject_token_type` | `requested_token_type`              | RTA grant       |
+//! |----------------------|-------------------------------------|-----------------|
+//! | access token         | absent, access token or RTAToken    | token exchange  |
+//! | RTAToken             | absent, access token or RTAToken    | delegation      |
+//! | RTAToken             | JWT                                 | JWT translation |
+//!
+//! The issued token's agent is named by `agent_id`, by an `actor_token` (an RTAToken
+//! the agent holds, presented on its own connection) or, except for delegation, by
+//! the connection's client certificate.
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rta_verify::caveat::AccessRequest;
+use crate::application::queries::validate_session_token;
+use crate::application::state::AppState;
+use crate::domain::clock::unix_now;
+use crate::infrastructure::quic_server::{
+    decode_token, Issued, RequestError, TokenExchangeRequest, TokenExchangeResponse,
+    GRANT_TYPE_DELEGATION, GRANT_TYPE_EXCHANGE, GRANT_TYPE_JWT_TRANSLATION,
+};
+use crate::infrastructure::quic_session::ConnectionSession;
+
+/// RFC 8693 token exchange.
+pub const GRANT_TYPE_TOKEN_EXCHANGE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+
+/// An OAuth access token issued by the identity provider (RFC 8693 §3).
+pub const TOKEN_TYPE_ACCESS_TOKEN: &str = "urn:ietf:params:oauth:token-type:access_token";
+/// A JWT (RFC 8693 §3).
+pub const TOKEN_TYPE_JWT: &str = "urn:ietf:params:oauth:token-type:jwt";
+/// A Base64-encoded RTAToken.
+pub const TOKEN_TYPE_RTATOKEN: &str = "urn:ietf:params:oauth:token-type:rtatoken";
+
+/// Rewrites an RFC 8693 request as the RTA grant it maps to.
+///
+/// An `actor_token` must validate against its session on this connection; the agent
+/// it was issued to becomes the request's `agent_id`.
+pub(crate) fn into_rta_request(
+    mut req: TokenExchangeRequest,
+    session: &ConnectionSession,
+    state: &AppState,
+) -> Result<TokenExchangeRequest> {
+    let subject = req.subject_token.take()
+        .ok_or_else(|| invalid("subject_token is required"))?;
+    let subject_type = req.subject_token_type.take()
+        .ok_or_else(|| invalid("subject_token_type is required"))?;
+    let jwt_requested = match req.requested_token_type.as_deref() {
+        None | Some(TOKEN_TYPE_ACCESS_TOKEN) | Some(TOKEN_TYPE_RTATOKEN) => false,
+        Some(TOKEN_TYPE_JWT) => true,
+        Some(other) => return Err(invalid(&format!("unsupported requested_token_type {:?}", other))),
+    };
+    let grant_type = match (subject_type.as_str(), jwt_requested) {
+        (TOKEN_TYPE_ACCESS_TOKEN, false) => {
+            req.oauth_token = subject;
+            GRANT_TYPE_EXCHANGE
+        }
+        (TOKEN_TYPE_RTATOKEN, false) => {
+            req.parent_token = Some(subject);
+            GRANT_TYPE_DELEGATION
+        }
+        (TOKEN_TYPE_RTATOKEN, true) => {
+            req.rtatoken = Some(subject);
+            GRANT_TYPE_JWT_TRANSLATION
+        }
+        (TOKEN_TYPE_ACCESS_TOKEN, true) => {
+            return Err(invalid("JWTs are only issued for RTAToken subjects"));
+        }
+        (other, _) => return Err(invalid(&format!("unsupported subject_token_type {:?}", other))),
+    };
+    req.grant_type = grant_type.to_string();
+    // The response carries a single token.
+    req.jwt = false;
+
+    if let Some(actor) = req.actor_token.take() {
+        if req.actor_token_type.as_deref() != Some(TOKEN_TYPE_RTATOKEN) {
+            return Err(invalid("actor_token must be an RTAToken"));
+        }
+        let actor = decode_token(&actor)?;
+        let binding = session.binding_evidence(None);
+        validate_session_token(&actor, state, Some(&binding), &AccessRequest::default())?;
+        let agent_id = actor.agent_id()
+            .ok_or_else(|| invalid("actor_token names no agent"))?;
+        if !req.agent_id.is_empty() && req.agent_id != agent_id {
+            return Err(invalid("agent_id does not match the actor_token"));
+        }
+        req.agent_id = agent_id.to_string();
+    }
+    if req.agent_id.is_empty() && !jwt_requested && session.agent_id().is_none() {
+        return Err(invalid("agent_id, actor_token or a client certificate is required"));
+    }
+    Ok(req)
+}
+
+/// The RFC 8693 §2.2.1 response for what the mapped grant issued.
+pub(crate) fn response(issued: &Issued, state: &AppState) -> TokenExchangeResponse {
+    let now = unix_now();
+    let (access_token, issued_token_type, expires_at, scope) = match issued {
+        Issued::Token(token) => (
+            STANDARD.encode(token.encode()),
+            TOKEN_TYPE_RTATOKEN,
+            token.expires_at(&state.token_config),
+            token.scopes().collect::<Vec<_>>().join(" "),
+        ),
+        Issued::Jwt(jwt) => (jwt.jwt.clone(), TOKEN_TYPE_JWT, jwt.expires_at, jwt.scope.clone()),
+    };
+    TokenExchangeResponse {
+        access_token: Some(access_token),
+        issued_token_type: Some(issued_token_type.to_string()),
+        token_type: Some("Bearer".to_string()),
+        expires_in: Some(expires_at.saturating_sub(now)),
+        scope: Some(scope).filter(|s| !s.is_empty()),
+        ..Default::default()
+    }
+}
+
+fn invalid(message: &str) -> anyhow::Error {
+    RequestError::Invalid(message.to_string()).into()
+}
@@ -0,0 +1,6 @@
+// src/lib.rs
+pub mod config;
+pub mod domain;
+pub mod application;
+pub mod infrastructure;
+pub mod token_exchange_quic;
@@ -1,16 +1,21 @@
 // src/main.rs
-mod config;
-mod domain;
-mod application;
-mod infrastructure;
-mod token_exchange_quic;
-
 use anyhow::Result;
 use std::net::SocketAddr;
+use std::sync::atomic::AtomicU32;
+use std::sync::Arc;
+use tokio::sync::broadcast;
 use tracing::info;
-use tracing_subscriber;
-use config::Settings;
-use infrastructure::quic_server::run_quic_token_exchange;
+use realtime_auth_idp::application::state::AppState;
+use realtime_auth_idp::config::Settings;
+use realtime_auth_idp::domain::clock::unix_now;
+use realtime_auth_idp::infrastructure::event_broker::run_event_subscriber;
+use realtime_auth_idp::infrastructure::http_server::run_http_server;
+use realtime_auth_idp::infrastructure::key_store::{load_keyring, run_key_rotation};
+use realtime_auth_idp::infrastructure::metrics::TransportMetrics;
+use realtime_auth_idp::infrastructure::nonce_cache::NonceCache;
+use realtime_auth_idp::infrastructure::pdp_adapter::PdpClient;
+use realtime_auth_idp::infrastructure::session_store::SessionStore;
+use realtime_auth_idp::token_exchange_quic::run_quic_token_exchange;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -25,17 +30,85 @@ async fn main() -> Result<()> {
     let settings = Settings::new("config/config.toml")
         .expect("Failed to load configuration");
 
+    // Load the persistent token signing keys and rotate them in the background.
+    let keyring = Arc::new(load_keyring(&settings.keyring)?);
+    // Domain events; agents' push channels subscribe to them, so a burst of revocations
+    // must fit before a slow subscriber lags.
+    let (events_tx, _) = broadcast::channel(1024);
+    let rotation_keyring = keyring.clone();
+    let keyring_config = settings.keyring.clone();
+    let rotation_events = events_tx.clone();
+    // Verifiers cache the key set for up to `jwks_max_age_secs`; new keys are listed that long before they sign.
+    let publish_ahead_secs = settings.http.jwks_max_age_secs;
+    tokio::spawn(async move {
+        if let Err(e) = run_key_rotation(rotation_keyring, keyring_config, publish_ahead_secs, rotation_events).await {
+            eprintln!("Signing key rotation error: {:?}", e);
+        }
+    });
+
+    let state = Arc::new(AppState {
+        token_config: settings.token.clone(),
+        jwt_config: settings.jwt.clone(),
+        idp_providers: settings.idp.clone(),
+        pdp: PdpClient::new(&settings.pdp)?,
+        introspection_config: settings.introspection.clone(),
+        keyring,
+        sessions: SessionStore::new(settings.token.session_ttl_secs()),
+        nonces: NonceCache::new(settings.token.nonce_ttl_secs, settings.token.max_nonces_per_connection),
+        policy_version: AtomicU32::new(settings.pdp.policy_version),
+        events: events_tx,
+        metrics: Arc::new(TransportMetrics::default()),
+    });
+
+    // Apply revocations, context changes and policy updates published on the event broker.
+    let broker_state = state.clone();
+    let redis_config = settings.redis.clone();
+    tokio::spawn(async move {
+        if let Err(e) = run_event_subscriber(redis_config, broker_state).await {
+            eprintln!("Event broker error: {:?}", e);
+        }
+    });
+
+    // Purge expired exchange challenges once per nonce lifetime.
+    let purge_state = state.clone();
+    tokio::spawn(async move {
+        let period = std::time::Duration::from_secs(purge_state.nonces.ttl_secs().max(1));
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            let now = unix_now();
+            purge_state.nonces.purge_expired(now);
+        }
+    });
+
+    // Purge sessions whose tokens can no longer be used or refreshed, once per token lifetime.
+    let purge_state = state.clone();
+    tokio::spawn(async move {
+        let period = std::time::Duration::from_secs(purge_state.token_config.max_age_secs.max(1));
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            let now = unix_now();
+            purge_state.sessions.purge_expired(now);
+        }
+    });
+
+    // Launch the HTTP endpoints (token introspection, verification keys, metrics).
+    let http_addr: SocketAddr = format!("{}:{}", settings.server.host, settings.http.port)
+        .parse()?;
+    let http_state = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = run_http_server(http_addr, &settings.http, http_state).await {
+            eprintln!("HTTP endpoint error: {:?}", e);
+        }
+    });
+
     // Launch the QUIC token exchange endpoint on a dedicated port (e.g., port 8082).
     let exchange_addr: SocketAddr = format!("{}:{}", settings.server.host, 8082)
         .parse()?;
+    let server_config = settings.server.clone();
     tokio::spawn(async move {
-        if let Err(e) = run_quic_token_exchange(
-            exchange_addr,
-            &settings.server,
-            &settings.idp,
-            &settings.token,
-            &settings.redis,
-        ).await {
+        if let Err(e) = run_quic_token_exchange(exchange_addr, &server_config, state).await {
             eprintln!("QUIC Token Exchange endpoint error: {:?}", e);
         }
     });
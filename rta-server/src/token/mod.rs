@@ -1,5 +0,0 @@
-mod token_trait;
-mod rta_token;
-
-pub use token_trait::Token;
-pub use rta_token::RTAToken;